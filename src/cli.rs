@@ -1,5 +1,6 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use clap_complete::engine::ArgValueCompleter;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "yati", about = "Git worktree manager with tmux integration")]
@@ -15,21 +16,209 @@ pub enum Command {
         /// Name of the branch to create
         #[arg(add = ArgValueCompleter::new(crate::completions::complete_create_branch))]
         branch_name: String,
+        /// Ref to branch from (branch, tag, commit, or e.g. origin/main). Defaults to `default_base` or HEAD
+        #[arg(long, add = ArgValueCompleter::new(crate::completions::complete_base_ref))]
+        from: Option<String>,
+        /// Leave the worktree, branch and session in place if a step fails, for debugging
+        #[arg(long)]
+        keep_on_failure: bool,
+        /// Print every step that would be taken, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't create a multiplexer session; start a shell in the worktree instead
+        #[arg(long)]
+        no_session: bool,
     },
-    /// Tear down the current yati worktree
+    /// Tear down yati worktrees (the current one by default)
     Teardown {
-        /// Force removal even with uncommitted changes
+        /// Branch names or project/branch targets to tear down
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_worktree_target))]
+        targets: Vec<String>,
+        /// Force removal even with uncommitted changes or unpushed commits
         #[arg(long)]
         force: bool,
+        /// Push unpushed commits to the remote before removing the branch
+        #[arg(long, conflicts_with = "archive")]
+        push: bool,
+        /// Keep unpushed commits under refs/yati/archive/ before removing the branch
+        #[arg(long)]
+        archive: bool,
+        /// Move the branch and uncommitted changes to the trash, restorable with `yati restore`
+        #[arg(long, conflicts_with_all = ["push", "archive"])]
+        trash: bool,
+        /// With --trash, also keep the ignored files listed in `copy_files`
+        #[arg(long, requires = "trash")]
+        with_ignored: bool,
+        /// Print every step that would be taken, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Keep the branch, only remove the worktree and session
+        #[arg(long, conflicts_with_all = ["push", "archive", "trash"])]
+        keep_branch: bool,
+    },
+    /// Bring back a worktree that was torn down with --trash
+    Restore {
+        /// Branch name or project/branch to restore
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_trash_target))]
+        target: String,
+        /// Restore the entry trashed at this timestamp instead of the latest (see `yati trash list`)
+        #[arg(long, value_name = "TIMESTAMP")]
+        at: Option<u64>,
+    },
+    /// Inspect or empty the trash
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Rename a worktree's branch, moving the worktree and its session along
+    Rename {
+        /// Branch name or project/branch to rename
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_worktree_target))]
+        target: String,
+        /// New branch name
+        new_branch: String,
+    },
+    /// Let yati manage a worktree made with `git worktree add` or another tool
+    Adopt {
+        /// Path of the worktree
+        path: PathBuf,
+        /// Move the worktree into the yati layout with `git worktree move`
+        #[arg(long = "move")]
+        move_into_layout: bool,
     },
     /// Activate an existing worktree by attaching to or creating its tmux session
     Activate {
-        /// Branch name or project/branch to activate
-        #[arg(add = ArgValueCompleter::new(crate::completions::complete_activate_target))]
-        target: String,
+        /// Branch name or project/branch to activate. Omit to pick one interactively
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_worktree_target))]
+        target: Option<String>,
+        /// Pick the worktree interactively, using TARGET as the initial query
+        #[arg(long)]
+        pick: bool,
+        /// Don't use a multiplexer session; start a shell in the worktree instead
+        #[arg(long)]
+        no_session: bool,
     },
     /// Deactivate the current yati session (switch to previous session or detach)
     Deactivate,
     /// List all yati-managed worktrees across all projects
+    List(ListArgs),
+    /// Tear down worktrees that are merged, whose remote branch is gone, or that are idle
+    #[command(alias = "gc")]
+    Prune(PruneArgs),
+    /// Show or set the description of a worktree
+    Describe {
+        /// Branch name or project/branch to describe
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_worktree_target))]
+        target: String,
+        /// New description; an empty string clears it. Omit to print the current one
+        text: Option<String>,
+    },
+    /// Show, add or remove tags of a worktree
+    Tag {
+        /// Branch name or project/branch to tag
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_worktree_target))]
+        target: String,
+        /// Tags to add (or remove with --remove)
+        tags: Vec<String>,
+        /// Remove the given tags instead of adding them
+        #[arg(long, short)]
+        remove: bool,
+    },
+    /// Check worktrees, git metadata, tmux sessions and yati's own state for drift
+    Doctor {
+        /// Repair and prune git worktrees, kill orphan sessions, remove empty directories
+        /// and drop state of missing worktrees
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the effective config for the current project
+    Show {
+        /// Show which file each value comes from
+        #[arg(long)]
+        origin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List trashed worktrees
     List,
+    /// Permanently delete trashed worktrees
+    Purge {
+        /// Only purge these project/branch targets
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_trash_target))]
+        targets: Vec<String>,
+        /// Only purge entries trashed more than DAYS ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Default)]
+pub struct ListArgs {
+    /// Also show worktrees yati doesn't manage, such as main worktrees and ones made
+    /// with `git worktree add`
+    #[arg(long)]
+    pub all: bool,
+    /// Print the worktrees as JSON
+    #[arg(long, conflicts_with = "format")]
+    pub json: bool,
+    /// Print one line per worktree from a template, e.g. "{session} {status}"
+    #[arg(long)]
+    pub format: Option<String>,
+    /// Only show worktrees of this project
+    #[arg(long, add = ArgValueCompleter::new(crate::completions::complete_project))]
+    pub project: Option<String>,
+    /// Only show worktrees with this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+    /// Only show worktrees with uncommitted changes
+    #[arg(long)]
+    pub dirty: bool,
+    /// Only show worktrees with no commits or session activity for DAYS (default 14)
+    #[arg(long, value_name = "DAYS", num_args = 0..=1, default_missing_value = "14")]
+    pub stale: Option<u64>,
+    /// Only show worktrees without a running tmux session
+    #[arg(long)]
+    pub no_session: bool,
+}
+
+#[derive(Args)]
+pub struct PruneArgs {
+    /// Only list what would be torn down
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Don't ask for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Also remove worktrees with uncommitted changes or unpushed commits
+    #[arg(long)]
+    pub force: bool,
+    /// Push unpushed commits to the remote before removing branches
+    #[arg(long, conflicts_with = "archive")]
+    pub push: bool,
+    /// Keep unpushed commits under refs/yati/archive/ before removing branches
+    #[arg(long)]
+    pub archive: bool,
+    /// Run `git fetch --prune` first, so merges and deleted remote branches are current
+    #[arg(long)]
+    pub fetch: bool,
+    /// Also prune worktrees with no commits or session activity for DAYS (default 30)
+    #[arg(long, value_name = "DAYS", num_args = 0..=1, default_missing_value = "30")]
+    pub idle: Option<u64>,
+    /// Only prune worktrees of this project
+    #[arg(long, add = ArgValueCompleter::new(crate::completions::complete_project))]
+    pub project: Option<String>,
 }
@@ -1,81 +1,152 @@
 use anyhow::{bail, Context, Result};
-use std::process::Command;
 
-use crate::{config, git, tmux};
+use crate::cli::ListArgs;
+use crate::commands::list;
+use crate::time::{format_age, now};
+use crate::picker::{self, Item};
+use crate::ports::PortRegistry;
+use crate::worktrees::{self, Worktree};
+use crate::projects::{self, Registry};
+use crate::vars::Vars;
+use crate::{config, git, hooks, metadata, multiplexer, paths, ports};
 
-/// Parse a target string into (project_name, branch_name).
+/// Parse a target string into the worktree it refers to.
 ///
-/// - If target contains `/`: first try interpreting the first segment as a project name.
-///   Check if `~/.yati/<first_segment>/<rest>` exists. If yes, use that split.
-///   Otherwise, fall back to treating the whole string as a branch in the current project.
+/// - If target contains `/`: first try interpreting it as `<project>/<branch>` of a
+///   discovered yati worktree. Otherwise, fall back to treating the whole string as a
+///   branch in the current project.
 /// - If target has no `/`: treat as a branch name in the current project.
-fn parse_target(target: &str) -> Result<(String, String)> {
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-    let yati_base = home.join(".yati");
-
-    if let Some(slash_pos) = target.find('/') {
-        let first_segment = &target[..slash_pos];
-        let rest = &target[slash_pos + 1..];
-
-        if !rest.is_empty() && yati_base.join(first_segment).join(rest).exists() {
-            return Ok((first_segment.to_string(), rest.to_string()));
-        }
+pub fn parse_target(target: &str) -> Result<Worktree> {
+    if target.contains('/')
+        && let Some(worktree) = worktrees::discover()?
+            .into_iter()
+            .find(|w| w.session_name() == target)
+    {
+        return Ok(worktree);
     }
 
     // Fall back to current project
-    let project = git::repo_name()
-        .context("Not in a git repository. Use <project>/<branch> syntax for cross-project activation.")?;
-    Ok((project, target.to_string()))
+    let main_worktree = git::main_worktree_root()
+        .context("Not in a git repository. Use <project>/<branch> syntax to target a worktree of another project.")?;
+    let config = config::load_config(&main_worktree)?;
+    let project = projects::name_for(&main_worktree, &config, &Registry::load()?)?;
+    let path = paths::worktree_path(&config, &project, target)?;
+    if path.exists() {
+        return Ok(Worktree {
+            project,
+            branch: target.to_string(),
+            path,
+            main_worktree,
+        });
+    }
+    // Adopted worktrees may live outside the layout
+    worktrees::discover()?
+        .into_iter()
+        .find(|w| w.project == project && w.branch == target)
+        .with_context(|| format!("No yati worktree found for '{}'", target))
 }
 
-pub fn run(target: &str) -> Result<()> {
-    let (project_name, branch_name) = parse_target(target)?;
-    let session_name = format!("{}/{}", project_name, branch_name);
-
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-    let worktree_path = home.join(".yati").join(&project_name).join(&branch_name);
-
-    if !worktree_path.exists() {
-        bail!("No yati worktree found for '{}'", target);
+/// Let the user pick a worktree, showing whether each one is dirty, whether its session
+/// is running and when it was last used.
+fn pick_target(query: Option<&str>) -> Result<Option<Worktree>> {
+    let mut all = worktrees::discover()?;
+    if all.is_empty() {
+        bail!("No yati-managed worktrees found");
     }
+    let ports = PortRegistry::load().unwrap_or_default();
+    let statuses = list::collect(&all, &ports, &ListArgs::default());
+    let width = statuses.iter().map(|s| s.session.len()).max().unwrap_or(0);
+    let items: Vec<Item> = statuses
+        .iter()
+        .map(|s| Item {
+            key: s.session.clone(),
+            label: format!(
+                "{:<width$}  {:<5}  {:<5}  {}",
+                s.session,
+                if s.dirty { "dirty" } else { "clean" },
+                if s.session_alive { "alive" } else { "-" },
+                s.last_used().map(format_age).unwrap_or_else(|| "-".to_string()),
+                width = width
+            ),
+            preview_dir: s.path.clone().into(),
+        })
+        .collect();
 
-    let entries = git::worktree_list_from(&worktree_path)?;
-    let main_worktree = entries.first().context("No worktrees found")?;
-    let config = config::load_config(&main_worktree.path)?;
+    let Some(session) = picker::pick(&items, query)? else {
+        return Ok(None);
+    };
+    let index = all
+        .iter()
+        .position(|w| w.session_name() == session)
+        .context("Picked worktree no longer exists")?;
+    Ok(Some(all.swap_remove(index)))
+}
 
-    if tmux::session_exists(&session_name) {
-        println!("Switching to existing session '{}'", session_name);
+pub fn run(target: Option<&str>, pick: bool, no_session: bool) -> Result<()> {
+    let worktree = match target {
+        Some(target) if !pick => parse_target(target)?,
+        query => match pick_target(query)? {
+            Some(worktree) => worktree,
+            None => return Ok(()),
+        },
+    };
+    activate(worktree, no_session, false)
+}
+
+/// Attach to the worktree's session, creating it (and running `post_create`) if needed.
+/// Without a multiplexer, only `post_activate` runs before entering the worktree, unless
+/// the worktree was just brought back (`fresh`, e.g. by `restore` or `adopt`), which
+/// always runs `post_create`.
+pub fn activate(worktree: Worktree, no_session: bool, fresh: bool) -> Result<()> {
+    let session_name = worktree.session_name();
+    let worktree_path = &worktree.path;
+    let base_ref = git::branch_base(&worktree.branch, &worktree.main_worktree);
+    let config = config::load_config(&worktree.main_worktree)?;
+    let reserved = ports::ensure(&session_name, &config)?;
+    let vars = Vars::new(&worktree, base_ref.as_deref()).with_ports(&reserved);
+    let config = vars.apply(&config);
+    let vars = vars.with_env(&config.env);
+    let mux = if no_session {
+        None
     } else {
-        for hook in &config.post_create {
-            println!("Running post_create hook: {}", hook);
-            let status = Command::new("sh")
-                .args(["-c", hook])
-                .current_dir(&worktree_path)
-                .status()
-                .with_context(|| format!("Failed to run hook: {}", hook))?;
-            if !status.success() {
-                eprintln!("Warning: post_create hook failed: {}", hook);
-            }
-        }
+        multiplexer::for_config(&config)
+    };
 
-        println!("Creating tmux session '{}'", session_name);
-        tmux::new_session(&session_name, &worktree_path)?;
-        tmux::setup_windows(&session_name, &worktree_path, &config.tmux.windows)?;
+    let session_exists = mux
+        .as_ref()
+        .is_some_and(|mux| mux.session_exists(&session_name));
+    // Without sessions there is no telling whether post_create ran since create, so
+    // it only runs for worktrees that were just brought back
+    if fresh || (mux.is_some() && !session_exists) {
+        hooks::run("post_create", &config.post_create, worktree_path, &vars)?;
     }
 
-    for hook in &config.post_activate {
-        println!("Running post_activate hook: {}", hook);
-        let status = Command::new("sh")
-            .args(["-c", hook])
-            .current_dir(&worktree_path)
-            .status()
-            .with_context(|| format!("Failed to run hook: {}", hook))?;
-        if !status.success() {
-            eprintln!("Warning: post_activate hook failed: {}", hook);
+    match &mux {
+        Some(mux) if session_exists => {
+            println!("Switching to existing session '{}'", session_name);
+            // New panes pick up `env` and ports changed since the session was created
+            mux.set_environment(&session_name, &vars.env())?;
         }
+        Some(mux) => {
+            println!("Creating {} session '{}'", mux.name(), session_name);
+            mux.new_session(&session_name, worktree_path, &vars.env())?;
+            mux.setup_windows(&session_name, worktree_path, &config.tmux.windows)?;
+        }
+        None => {}
     }
 
-    tmux::attach_or_switch(&session_name)?;
+    hooks::run("post_activate", &config.post_activate, worktree_path, &vars)?;
+
+    if let Err(e) = metadata::update(&worktree, |meta| {
+        meta.last_activated = Some(now());
+    }) {
+        eprintln!("Warning: failed to record worktree metadata: {}", e);
+    }
+
+    match mux {
+        Some(mux) => mux.attach_or_switch(&session_name)?,
+        None => multiplexer::enter_without_session(worktree_path, &vars.env())?,
+    }
 
     Ok(())
 }
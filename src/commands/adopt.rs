@@ -0,0 +1,82 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::commands::activate;
+use crate::time::now;
+use crate::worktrees::{self, Worktree};
+use crate::{config, git, metadata, paths, projects};
+
+pub fn run(path: &Path, move_into_layout: bool) -> Result<()> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("No such directory: {}", path.display()))?;
+    let main_worktree = git::main_worktree_of(&path)
+        .with_context(|| format!("{} is not in a git repository", path.display()))?;
+    if main_worktree == path {
+        bail!(
+            "{} is the main worktree of its repository; only linked worktrees can be adopted",
+            path.display()
+        );
+    }
+    let Some(entry) = git::worktree_list_from(&main_worktree)?
+        .into_iter()
+        .find(|e| e.path == path)
+    else {
+        bail!("{} is not the top level of a git worktree", path.display());
+    };
+    if entry.branch.is_empty() {
+        bail!(
+            "{} has a detached HEAD; check out a branch before adopting it",
+            path.display()
+        );
+    }
+    if let Some(worktree) = worktrees::identify(&path)? {
+        bail!(
+            "{} is already managed by yati as '{}'",
+            path.display(),
+            worktree.session_name()
+        );
+    }
+
+    let config = config::load_config(&main_worktree)?;
+    let project = projects::register(&main_worktree, &config)?;
+    let mut worktree = Worktree {
+        project,
+        branch: entry.branch,
+        path,
+        main_worktree,
+    };
+
+    if move_into_layout {
+        let target = paths::worktree_path(&config, &worktree.project, &worktree.branch)?;
+        if target.exists() {
+            bail!("Worktree path already exists: {}", target.display());
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        println!(
+            "Moving worktree from {} to {}",
+            worktree.path.display(),
+            target.display()
+        );
+        git::worktree_move(&worktree.path, &target, &worktree.main_worktree)?;
+        worktree.path = target;
+    }
+
+    if git::branch_base(&worktree.branch, &worktree.main_worktree).is_none()
+        && let Some(base) = &config.default_base
+    {
+        git::set_branch_base(&worktree.branch, base, &worktree.main_worktree)?;
+    }
+    let outside_layout = !move_into_layout;
+    metadata::update(&worktree, |meta| {
+        meta.created.get_or_insert(now());
+        meta.command = Some(metadata::command_line());
+        meta.path = outside_layout.then(|| worktree.path.clone());
+    })?;
+    println!("Adopted {} as '{}'", worktree.path.display(), worktree.session_name());
+
+    activate::activate(worktree, false, true)
+}
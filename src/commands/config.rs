@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use crate::config::{self, Layer, Merged, MergedValue};
+use crate::git;
+
+pub fn show(origin: bool) -> Result<()> {
+    let repo_root = git::main_worktree_root()?;
+
+    if !origin {
+        let config = config::load_config(&repo_root)?;
+        let text = toml::to_string_pretty(&config).context("Failed to serialize config")?;
+        print!("{}", text);
+        return Ok(());
+    }
+
+    let entries = origins(config::load_layers(&repo_root)?)?;
+    let width = entries
+        .iter()
+        .map(|(key, value, _)| key.len() + value.len() + 3)
+        .max()
+        .unwrap_or(0);
+    for (key, value, source) in &entries {
+        let line = format!("{} = {}", key, value);
+        println!("{:<width$}  # {}", line, source, width = width);
+    }
+    Ok(())
+}
+
+/// Effective config values as `(key, value, source)`, merged with the same rules as
+/// `load_config`.
+fn origins(layers: Vec<Layer>) -> Result<Vec<(String, String, String)>> {
+    let mut merged = Merged::new();
+    for Layer { source, config } in layers {
+        config::merge_layer(&mut merged, &config, |value| (value.to_string(), source.clone()))?;
+    }
+
+    let mut entries = Vec::new();
+    for (key, value) in merged {
+        match value {
+            MergedValue::Scalar((value, source)) => entries.push((key, value, source)),
+            MergedValue::List(items) => {
+                for (i, (value, source)) in items.into_iter().enumerate() {
+                    entries.push((format!("{}[{}]", key, i), value, source));
+                }
+            }
+            MergedValue::Named(items) => {
+                for (i, (_, (value, source))) in items.into_iter().enumerate() {
+                    entries.push((format!("{}[{}]", key, i), value, source));
+                }
+            }
+            MergedValue::Map(map) => {
+                for (name, (value, source)) in map {
+                    entries.push((format!("{}.{}", key, name), value, source));
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
@@ -1,20 +1,34 @@
-use anyhow::{bail, Context, Result};
-use std::process::Command;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-use crate::{config, copy, git, tmux};
+use crate::config::{Config, WindowConfig};
+use crate::git::BranchStart;
+use crate::projects::Registry;
+use crate::vars::Vars;
+use crate::worktrees::Worktree;
+use crate::time::now;
+use crate::multiplexer::{self, Multiplexer};
+use crate::{config, copy, git, hooks, metadata, paths, ports, projects};
 
-pub fn run(branch_name: &str) -> Result<()> {
+pub fn run(
+    branch_name: &str,
+    from: Option<&str>,
+    keep_on_failure: bool,
+    dry_run: bool,
+    no_session: bool,
+) -> Result<()> {
     let repo_root = git::main_worktree_root()?;
-    let project_name = git::main_repo_name()?;
     git::validate_branch_name(branch_name)?;
 
     let config = config::load_config(&repo_root)?;
+    let project_name = if dry_run {
+        projects::name_for(&repo_root, &config, &Registry::load()?)?
+    } else {
+        projects::register(&repo_root, &config)?
+    };
 
-    let yati_base = dirs::home_dir()
-        .context("Could not determine home directory")?
-        .join(".yati")
-        .join(&project_name);
-    let worktree_path = yati_base.join(branch_name);
+    let worktree_path = paths::worktree_path(&config, &project_name, branch_name)?;
 
     if worktree_path.exists() {
         bail!(
@@ -23,32 +37,423 @@ pub fn run(branch_name: &str) -> Result<()> {
         );
     }
 
-    println!("Creating worktree at {}", worktree_path.display());
-    git::worktree_add(&worktree_path, branch_name)?;
-
-    if !config.copy_files.is_empty() {
-        println!("Copying configured files...");
-        copy::copy_files(&repo_root, &worktree_path, &config.copy_files, &config.exclude)?;
+    // An explicit --from always wins for new branches. Otherwise a branch that only exists
+    // on a remote is checked out tracking it, and anything else forks from `default_base`
+    // or whatever is checked out here.
+    let start = if git::branch_exists(branch_name) {
+        if let Some(from) = from {
+            bail!(
+                "Branch '{}' already exists, cannot create it from '{}'",
+                branch_name,
+                from
+            );
+        }
+        BranchStart::Existing
+    } else if let Some(from) = from {
+        BranchStart::From(from.to_string())
+    } else if let Some(upstream) = git::find_remote_branch(branch_name)? {
+        BranchStart::Track(upstream)
+    } else {
+        match &config.default_base {
+            Some(base) => BranchStart::From(base.clone()),
+            None => BranchStart::From(git::current_ref()?),
+        }
+    };
+    if let BranchStart::From(base) = &start {
+        git::validate_ref(base)?;
     }
 
-    for hook in &config.post_create {
-        println!("Running post_create hook: {}", hook);
-        let status = Command::new("sh")
-            .args(["-c", hook])
-            .current_dir(&worktree_path)
-            .status()
-            .with_context(|| format!("Failed to run hook: {}", hook))?;
-        if !status.success() {
-            eprintln!("Warning: post_create hook failed: {}", hook);
+    let worktree = Worktree {
+        project: project_name,
+        branch: branch_name.to_string(),
+        path: worktree_path.clone(),
+        main_worktree: repo_root.clone(),
+    };
+    let mux = if no_session {
+        None
+    } else {
+        multiplexer::for_config(&config)
+    };
+    let mut create = Create::new(&worktree, &start, &config, mux.as_deref())?;
+    let actions = plan(&create);
+
+    if dry_run {
+        println!("Dry run for '{}', nothing will be changed:", create.session_name);
+        for action in &actions {
+            action.print(&create);
+        }
+        match create.mux {
+            Some(mux) if mux.is_inside() => {
+                println!("  - Switch to session '{}'", create.session_name)
+            }
+            Some(_) => println!("  - Attach to session '{}'", create.session_name),
+            None => println!(
+                "  - Start $SHELL in {} (or print the path when not run from a terminal)",
+                worktree_path.display()
+            ),
         }
+        return Ok(());
     }
 
-    let session_name = format!("{}/{}", project_name, branch_name);
+    let mut steps = Vec::new();
+    let result = actions
+        .iter()
+        .try_for_each(|action| action.run(&mut create, &mut steps));
+    if let Err(e) = result {
+        if keep_on_failure {
+            eprintln!(
+                "Create failed; leaving partial state in place (--keep-on-failure): {}",
+                worktree_path.display()
+            );
+        } else if !steps.is_empty() {
+            eprintln!("Create failed, rolling back...");
+            rollback(&steps, &repo_root, mux.as_deref());
+            if let Some(parent) = worktree_path.parent() {
+                paths::cleanup_empty_parents(parent, &paths::project_root(&config)?);
+            }
+        }
+        return Err(e);
+    }
 
-    println!("Creating tmux session '{}'", session_name);
-    tmux::new_session(&session_name, &worktree_path)?;
-    tmux::setup_windows(&session_name, &worktree_path, &config.tmux.windows)?;
-    tmux::attach_or_switch(&session_name)?;
+    match create.mux {
+        Some(mux) => mux.attach_or_switch(&create.session_name)?,
+        None => multiplexer::enter_without_session(&worktree_path, &create.vars.env())?,
+    }
 
     Ok(())
 }
+
+/// A completed step of `create` that has to be undone if a later step fails.
+enum Step {
+    CreatedBranch(String),
+    AddedWorktree(PathBuf),
+    ReservedPorts(String),
+    CreatedSession(String),
+}
+
+/// Undo completed steps in reverse order. Failures are reported but don't stop the rollback.
+fn rollback(steps: &[Step], repo_root: &Path, mux: Option<&dyn Multiplexer>) {
+    for step in steps.iter().rev() {
+        match step {
+            Step::CreatedSession(name) => {
+                let Some(mux) = mux else { continue };
+                println!("Killing {} session '{}'", mux.name(), name);
+                if let Err(e) = mux.kill_session(name) {
+                    eprintln!("Warning: failed to kill session '{}': {}", name, e);
+                }
+            }
+            Step::ReservedPorts(session) => {
+                println!("Releasing ports of '{}'", session);
+                if let Err(e) = ports::release(session) {
+                    eprintln!("Warning: failed to release ports: {}", e);
+                }
+            }
+            Step::AddedWorktree(path) => {
+                println!("Removing worktree at {}", path.display());
+                if let Err(e) = git::worktree_remove(path, true, repo_root) {
+                    eprintln!("Warning: failed to remove worktree: {}", e);
+                }
+                if path.exists() {
+                    let _ = std::fs::remove_dir_all(path);
+                }
+            }
+            Step::CreatedBranch(branch) => {
+                println!("Deleting branch '{}'", branch);
+                if let Err(e) = git::branch_delete(branch, true, repo_root) {
+                    eprintln!("Warning: failed to delete branch '{}': {}", branch, e);
+                }
+            }
+        }
+    }
+}
+
+/// The base recorded for a new branch. Remote branches have no base of their own, so
+/// they fall back to the configured one if any.
+fn recorded_base<'a>(start: &'a BranchStart, config: &'a Config) -> Option<&'a str> {
+    match start {
+        BranchStart::Existing => None,
+        BranchStart::From(base) => Some(base.as_str()),
+        BranchStart::Track(_) => config.default_base.as_deref(),
+    }
+}
+
+/// Everything the steps of a create need to know about the new worktree.
+struct Create<'a> {
+    worktree: &'a Worktree,
+    start: &'a BranchStart,
+    /// The config as loaded, before placeholders are filled in.
+    loaded: &'a Config,
+    mux: Option<&'a dyn Multiplexer>,
+    session_name: String,
+    base_ref: Option<String>,
+    /// The ports reserved so far: a preview until `Action::ReservePorts` runs.
+    reserved: Vec<u16>,
+    vars: Vars,
+    /// `loaded` with placeholders filled in from `vars`.
+    config: Config,
+}
+
+impl<'a> Create<'a> {
+    fn new(
+        worktree: &'a Worktree,
+        start: &'a BranchStart,
+        loaded: &'a Config,
+        mux: Option<&'a dyn Multiplexer>,
+    ) -> Result<Create<'a>> {
+        let session_name = worktree.session_name();
+        let base_ref = recorded_base(start, loaded)
+            .map(str::to_string)
+            .or_else(|| git::branch_base(&worktree.branch, &worktree.main_worktree));
+        let reserved = ports::preview(&session_name, loaded)?;
+        let mut create = Create {
+            worktree,
+            start,
+            loaded,
+            mux,
+            session_name,
+            base_ref,
+            reserved: Vec::new(),
+            vars: Vars::new(worktree, None),
+            config: Config::default(),
+        };
+        create.set_ports(reserved);
+        Ok(create)
+    }
+
+    /// Fill in placeholders again for `reserved`.
+    fn set_ports(&mut self, reserved: Vec<u16>) {
+        let vars = Vars::new(self.worktree, self.base_ref.as_deref()).with_ports(&reserved);
+        self.config = vars.apply(self.loaded);
+        self.vars = vars.with_env(&self.config.env);
+        self.reserved = reserved;
+    }
+}
+
+/// One step of a create, printed by `--dry-run` and run otherwise.
+enum Action {
+    AddWorktree,
+    RecordBase(String),
+    ReservePorts,
+    CopyFiles,
+    /// Run the `post_create` hook at this index.
+    Hook(usize),
+    CreateSession,
+    SetupWindows,
+    RecordMetadata,
+}
+
+/// The steps creating the worktree takes, in order, short of attaching to it.
+fn plan(create: &Create) -> Vec<Action> {
+    let mut actions = vec![Action::AddWorktree];
+    if let Some(base) = recorded_base(create.start, create.loaded) {
+        actions.push(Action::RecordBase(base.to_string()));
+    }
+    actions.push(Action::ReservePorts);
+    if !create.config.copy_files.is_empty() {
+        actions.push(Action::CopyFiles);
+    }
+    actions.extend((0..create.config.post_create.len()).map(Action::Hook));
+    if create.mux.is_some() {
+        actions.push(Action::CreateSession);
+        actions.push(Action::SetupWindows);
+    }
+    actions.push(Action::RecordMetadata);
+    actions
+}
+
+impl Action {
+    /// Describe the step for `--dry-run`.
+    fn print(&self, create: &Create) {
+        let worktree = create.worktree;
+        let branch = &worktree.branch;
+        let path = &worktree.path;
+        let config = &create.config;
+        match self {
+            Action::AddWorktree => {
+                match create.start {
+                    BranchStart::Existing => println!("  - Check out existing branch '{}'", branch),
+                    BranchStart::From(base) => {
+                        println!("  - Create branch '{}' from '{}'", branch, base)
+                    }
+                    BranchStart::Track(upstream) => {
+                        println!("  - Create branch '{}' tracking '{}'", branch, upstream)
+                    }
+                }
+                println!("  - Add worktree at {}", path.display());
+            }
+            Action::RecordBase(base) => {
+                println!("  - Record base '{}' in branch.{}.yatiBase", base, branch)
+            }
+            Action::ReservePorts => {
+                if !create.reserved.is_empty() {
+                    println!("  - Reserve ports {}", ports::join(&create.reserved));
+                }
+            }
+            Action::CopyFiles => {
+                for entry in &config.copy_files {
+                    let src = worktree.main_worktree.join(entry);
+                    if src.exists() {
+                        println!("  - Copy {} to {}", src.display(), path.join(entry).display());
+                    } else {
+                        println!("  - Skip {} (does not exist)", src.display());
+                    }
+                }
+                if !config.exclude.is_empty() {
+                    println!("      excluding {}", config.exclude.join(", "));
+                }
+            }
+            Action::Hook(i) => println!(
+                "  - Run post_create hook in {}: {}",
+                path.display(),
+                config.post_create[*i]
+            ),
+            Action::CreateSession => {
+                let Some(mux) = create.mux else { return };
+                println!(
+                    "  - Create {} session '{}' in {}",
+                    mux.name(),
+                    create.session_name,
+                    path.display()
+                );
+                if !config.env.is_empty() {
+                    println!("      with {}", format_env(&config.env));
+                }
+            }
+            Action::SetupWindows => print_windows(path, &config.tmux.windows),
+            Action::RecordMetadata => {}
+        }
+    }
+
+    fn run(&self, create: &mut Create, steps: &mut Vec<Step>) -> Result<()> {
+        let worktree = create.worktree;
+        let repo_root = &worktree.main_worktree;
+        let branch = &worktree.branch;
+        let path = &worktree.path;
+        match self {
+            Action::AddWorktree => {
+                println!("Creating worktree at {}", path.display());
+                let added = git::worktree_add(path, branch, create.start, repo_root);
+                // git creates the branch before checking it out, so a failed checkout
+                // can leave it behind
+                if !matches!(create.start, BranchStart::Existing) && git::branch_exists(branch) {
+                    steps.push(Step::CreatedBranch(branch.to_string()));
+                }
+                added?;
+                steps.push(Step::AddedWorktree(path.to_path_buf()));
+                match create.start {
+                    BranchStart::Existing => {}
+                    BranchStart::From(base) => println!("Branched '{}' from '{}'", branch, base),
+                    BranchStart::Track(upstream) => {
+                        println!("Branch '{}' set up to track '{}'", branch, upstream)
+                    }
+                }
+            }
+            Action::RecordBase(base) => {
+                if let Err(e) = git::set_branch_base(branch, base, repo_root) {
+                    eprintln!("Warning: failed to record base ref: {}", e);
+                }
+            }
+            Action::ReservePorts => {
+                let reserved = ports::ensure(&create.session_name, create.loaded)?;
+                if !reserved.is_empty() {
+                    println!("Reserved ports {}", ports::join(&reserved));
+                    steps.push(Step::ReservedPorts(create.session_name.clone()));
+                }
+                // Another yati may have taken the previewed ports in the meantime
+                if reserved != create.reserved {
+                    create.set_ports(reserved);
+                }
+            }
+            Action::CopyFiles => {
+                println!("Copying configured files...");
+                let config = &create.config;
+                copy::copy_files(repo_root, path, &config.copy_files, &config.exclude)?;
+            }
+            Action::Hook(i) => hooks::run(
+                "post_create",
+                std::slice::from_ref(&create.config.post_create[*i]),
+                path,
+                &create.vars,
+            )?,
+            Action::CreateSession => {
+                let Some(mux) = create.mux else {
+                    return Ok(());
+                };
+                println!("Creating {} session '{}'", mux.name(), create.session_name);
+                mux.new_session(&create.session_name, path, &create.vars.env())?;
+                steps.push(Step::CreatedSession(create.session_name.clone()));
+            }
+            Action::SetupWindows => {
+                if let Some(mux) = create.mux {
+                    mux.setup_windows(&create.session_name, path, &create.config.tmux.windows)?;
+                }
+            }
+            Action::RecordMetadata => {
+                let created = now();
+                if let Err(e) = metadata::update(worktree, |meta| {
+                    meta.created = Some(created);
+                    meta.command = Some(metadata::command_line());
+                    meta.last_activated = Some(created);
+                }) {
+                    eprintln!("Warning: failed to record worktree metadata: {}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Describe the windows `setup_windows` would create in a session at `path`.
+fn print_windows(path: &Path, windows: &[WindowConfig]) {
+    for (i, window) in windows.iter().enumerate() {
+        let action = if i == 0 {
+            format!("Rename the first window to '{}'", window.name)
+        } else {
+            format!("Create window '{}'", window.name)
+        };
+        let window_dir = match &window.cwd {
+            Some(cwd) => path.join(cwd),
+            None => path.to_path_buf(),
+        };
+        match &window.command {
+            Some(command) => println!(
+                "  - {} in {} running `{}`",
+                action,
+                window_dir.display(),
+                command
+            ),
+            None => println!("  - {} in {}", action, window_dir.display()),
+        }
+        if !window.env.is_empty() {
+            println!("      with {}", format_env(&window.env));
+        }
+        for (j, pane) in window.panes.iter().enumerate() {
+            let dir = match &pane.cwd {
+                Some(cwd) => path.join(cwd),
+                None => window_dir.clone(),
+            };
+            let mut line = format!("pane {} in {}", j, dir.display());
+            if let Some(command) = &pane.command {
+                line.push_str(&format!(" running `{}`", command));
+            }
+            if let Some(size) = &pane.size {
+                line.push_str(&format!(", size {}", size));
+            }
+            if pane.focus {
+                line.push_str(", focused");
+            }
+            println!("      {}", line);
+        }
+        if let Some(layout) = &window.layout {
+            println!("      layout {}", layout);
+        }
+    }
+}
+
+/// `env` as `NAME=value, ...` for the dry run.
+fn format_env(env: &BTreeMap<String, String>) -> String {
+    env.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
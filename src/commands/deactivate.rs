@@ -1,34 +1,20 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 
-use crate::tmux;
+use crate::{multiplexer, worktrees};
 
 pub fn run() -> Result<()> {
-    if !tmux::is_in_tmux() {
-        bail!("Not inside a tmux session");
-    }
-
-    let cwd = std::env::current_dir().context("Failed to get current directory")?;
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-    let yati_base = home.join(".yati");
+    let mux = multiplexer::current().context("Not inside a tmux or Zellij session")?;
 
-    let relative = cwd
-        .strip_prefix(&yati_base)
-        .map_err(|_| anyhow::anyhow!("Not in a yati worktree (current directory is not under ~/.yati/)"))?;
-
-    let components: Vec<_> = relative.components().collect();
-    if components.len() < 2 {
-        bail!("Cannot determine project/branch from path: {}", cwd.display());
-    }
-    let project = components[0].as_os_str().to_string_lossy();
-    let branch = components[1].as_os_str().to_string_lossy();
+    let worktree = worktrees::current()?;
+    let session_name = worktree.session_name();
 
     // Switch to the session we came from, if there was one
-    if tmux::switch_to_previous_session() {
-        println!("Deactivated '{}/{}'", project, branch);
+    if mux.switch_to_previous_session() {
+        println!("Deactivated '{}'", session_name);
     } else {
-        // No other session — detach from tmux to return to the original terminal
-        tmux::detach()?;
-        println!("Deactivated '{}/{}', detached from tmux", project, branch);
+        // No other session — detach to return to the original terminal
+        mux.detach()?;
+        println!("Deactivated '{}', detached from {}", session_name, mux.name());
     }
 
     Ok(())
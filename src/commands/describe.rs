@@ -0,0 +1,27 @@
+use anyhow::Result;
+
+use crate::commands::activate::parse_target;
+use crate::metadata;
+
+/// Set the description of `target`, clear it if `text` is empty, or print it if no
+/// text is given.
+pub fn run(target: &str, text: Option<&str>) -> Result<()> {
+    let worktree = parse_target(target)?;
+    let Some(text) = text else {
+        if let Some(description) = metadata::get(&worktree)?.description {
+            println!("{}", description);
+        }
+        return Ok(());
+    };
+
+    let text = text.trim();
+    metadata::update(&worktree, |meta| {
+        meta.description = (!text.is_empty()).then(|| text.to_string());
+    })?;
+    if text.is_empty() {
+        println!("Cleared description of '{}'", worktree.session_name());
+    } else {
+        println!("Described '{}'", worktree.session_name());
+    }
+    Ok(())
+}
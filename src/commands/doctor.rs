@@ -0,0 +1,367 @@
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::commands::prune;
+use crate::config::{self, MultiplexerKind};
+use crate::metadata::{self, Metadata};
+use crate::ports::{self, PortRegistry};
+use crate::projects::Registry;
+use crate::worktrees::{self, Worktree};
+use crate::{git, multiplexer, paths, trash};
+
+/// How `yati doctor --fix` resolves a problem.
+enum Fix {
+    Repair { path: PathBuf, repo: PathBuf },
+    Prune { repo: PathBuf },
+    KillSession(MultiplexerKind, String),
+    /// Remove an empty directory and its empty parents, up to the root it was found in.
+    RemoveDir { dir: PathBuf, root: PathBuf },
+    ReleasePorts(String),
+    ForgetMetadata { project: String, branch: String },
+}
+
+impl Fix {
+    fn describe(&self) -> String {
+        match self {
+            Fix::Repair { path, .. } => format!("git worktree repair {}", path.display()),
+            Fix::Prune { repo } => format!("git worktree prune in {}", repo.display()),
+            Fix::KillSession(kind, session) => format!(
+                "kill {} session '{}'",
+                multiplexer::backend(*kind).map_or("", |mux| mux.name()),
+                session
+            ),
+            Fix::RemoveDir { dir, .. } => format!("remove {}", dir.display()),
+            Fix::ReleasePorts(session) => format!("release the ports of '{}'", session),
+            Fix::ForgetMetadata { project, branch } => {
+                format!("forget the metadata of '{}/{}'", project, branch)
+            }
+        }
+    }
+
+    fn apply(&self) -> Result<()> {
+        match self {
+            Fix::Repair { path, repo } => git::worktree_repair(path, repo),
+            Fix::Prune { repo } => git::worktree_prune(repo),
+            Fix::KillSession(kind, session) => match multiplexer::backend(*kind) {
+                Some(mux) => mux.kill_session(session),
+                None => Ok(()),
+            },
+            Fix::RemoveDir { dir, root } => {
+                paths::cleanup_empty_parents(dir, root);
+                if dir.exists() {
+                    bail!("{} is no longer empty", dir.display());
+                }
+                Ok(())
+            }
+            Fix::ReleasePorts(session) => ports::release(session),
+            Fix::ForgetMetadata { project, branch } => metadata::forget(project, branch),
+        }
+    }
+}
+
+struct Problem {
+    description: String,
+    /// `None` if the problem needs a closer look than doctor can give it.
+    fix: Option<Fix>,
+}
+
+impl Problem {
+    fn new(description: String, fix: Option<Fix>) -> Problem {
+        Problem { description, fix }
+    }
+}
+
+/// Walk `dir`, collecting worktree directories (those with a `.git` file) and empty
+/// directories. Worktrees and `skip` aren't descended into.
+fn scan(dir: &Path, skip: &[PathBuf], worktrees: &mut Vec<PathBuf>, empty: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let entries: Vec<_> = entries.filter_map(|e| e.ok()).collect();
+    if entries.is_empty() {
+        empty.push(dir.to_path_buf());
+    }
+    for entry in entries {
+        if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+        let path = entry.path();
+        if skip.contains(&path) {
+            continue;
+        }
+        if path.join(".git").is_file() {
+            worktrees.push(path);
+        } else {
+            scan(&path, skip, worktrees, empty);
+        }
+    }
+}
+
+/// The administrative directory a worktree's `.git` file points to.
+fn gitdir(worktree: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(worktree.join(".git")).ok()?;
+    let gitdir = Path::new(contents.strip_prefix("gitdir:")?.trim());
+    Some(worktree.join(gitdir))
+}
+
+/// Problems with a worktree directory found under the yati root.
+fn check_worktree_dir(dir: &Path) -> Option<Problem> {
+    let gitdir = gitdir(dir)?;
+    if !gitdir.exists() {
+        return Some(Problem::new(
+            format!(
+                "{} has no git metadata ({} is gone); check it for work to keep and delete it",
+                dir.display(),
+                gitdir.display()
+            ),
+            None,
+        ));
+    }
+    let Ok(main_worktree) = git::main_worktree_of(dir) else {
+        return Some(Problem::new(
+            format!("{} is not a valid git worktree", dir.display()),
+            None,
+        ));
+    };
+    let listed = git::worktree_list_from(&main_worktree)
+        .is_ok_and(|entries| entries.iter().any(|e| e.path == dir));
+    if listed {
+        return None;
+    }
+    Some(Problem::new(
+        format!(
+            "{} is not listed by git in {}",
+            dir.display(),
+            main_worktree.display()
+        ),
+        Some(Fix::Repair {
+            path: dir.to_path_buf(),
+            repo: main_worktree,
+        }),
+    ))
+}
+
+/// Worktrees git knows about whose directory is gone.
+fn check_git_worktrees(repos: &[PathBuf], problems: &mut Vec<Problem>) {
+    for repo in repos {
+        let Ok(entries) = git::worktree_list_from(repo) else {
+            continue;
+        };
+        for entry in entries.into_iter().skip(1) {
+            if entry.path.exists() {
+                continue;
+            }
+            problems.push(Problem::new(
+                format!(
+                    "git lists worktree {} ({}) in {}, but the directory is gone",
+                    entry.path.display(),
+                    if entry.branch.is_empty() { "detached" } else { &entry.branch },
+                    repo.display()
+                ),
+                Some(Fix::Prune { repo: repo.clone() }),
+            ));
+        }
+    }
+}
+
+/// Worktrees whose checked-out branch doesn't match the branch their path names. The
+/// sessions of checked-out branches are added to `sessions`, so their state isn't
+/// mistaken for leftovers.
+fn check_branches(
+    managed: &[Worktree],
+    sessions: &mut BTreeSet<String>,
+    problems: &mut Vec<Problem>,
+) {
+    for worktree in managed {
+        // Detached HEADs are normal mid-rebase
+        let Some(branch) = git::checked_out_branch(&worktree.path) else {
+            continue;
+        };
+        if branch != worktree.branch {
+            sessions.insert(format!("{}/{}", worktree.project, branch));
+            problems.push(Problem::new(
+                format!(
+                    "{} has '{}' checked out, but yati expects '{}' there; check out '{}' \
+                     or move the worktree",
+                    worktree.path.display(),
+                    branch,
+                    worktree.branch,
+                    worktree.branch
+                ),
+                None,
+            ));
+        }
+    }
+}
+
+/// Sessions, ports and metadata of worktrees that no longer exist, i.e. that don't
+/// belong to one of `sessions`.
+fn check_leftovers(
+    managed: &[Worktree],
+    sessions: &BTreeSet<String>,
+    registry: &Registry,
+    problems: &mut Vec<Problem>,
+) {
+    let projects: BTreeSet<&str> = registry
+        .projects
+        .keys()
+        .map(String::as_str)
+        .chain(managed.iter().map(|w| w.project.as_str()))
+        .collect();
+
+    for (kind, mux) in multiplexer::all() {
+        let mut running: Vec<String> = mux.sessions().into_keys().collect();
+        running.sort();
+        for session in running {
+            let is_yati = session
+                .split_once('/')
+                .is_some_and(|(project, _)| projects.contains(project));
+            if is_yati && !sessions.contains(&session) {
+                problems.push(Problem::new(
+                    format!("{} session '{}' has no worktree", mux.name(), session),
+                    Some(Fix::KillSession(kind, session)),
+                ));
+            }
+        }
+    }
+
+    let ports = PortRegistry::load().unwrap_or_default();
+    for (session, held) in &ports.allocations {
+        if !sessions.contains(session) {
+            problems.push(Problem::new(
+                format!(
+                    "ports {} are held by missing worktree '{}'",
+                    ports::join(held),
+                    session
+                ),
+                Some(Fix::ReleasePorts(session.clone())),
+            ));
+        }
+    }
+
+    for (project, branch) in Metadata::load_all().entries() {
+        if !sessions.contains(&format!("{}/{}", project, branch)) {
+            problems.push(Problem::new(
+                format!("metadata is recorded for missing worktree '{}/{}'", project, branch),
+                Some(Fix::ForgetMetadata { project, branch }),
+            ));
+        }
+    }
+}
+
+/// The yati root and the `worktree_root` of every registered project, outermost first.
+fn roots(registry: &Registry) -> Result<Vec<PathBuf>> {
+    let mut roots = vec![paths::yati_root()?];
+    for entry in registry.projects.values() {
+        if let Ok(config) = config::load_config(&entry.path)
+            && let Ok(root) = paths::project_root(&config)
+        {
+            roots.push(root);
+        }
+    }
+    roots.sort();
+    roots.dedup();
+    // Roots inside another root are scanned with it
+    let mut outermost: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        if !outermost.iter().any(|r| root.starts_with(r)) {
+            outermost.push(root);
+        }
+    }
+    Ok(outermost)
+}
+
+fn diagnose() -> Result<Vec<Problem>> {
+    let managed = worktrees::discover()?;
+    let registry = Registry::load()?;
+    let mut problems = Vec::new();
+
+    // yati's own state lives under the root next to the worktrees
+    let state = [metadata::metadata_dir()?, trash::trash_dir()?];
+    for root in roots(&registry)? {
+        let mut dirs = Vec::new();
+        let mut empty = Vec::new();
+        scan(&root, &state, &mut dirs, &mut empty);
+        problems.extend(dirs.iter().filter_map(|dir| check_worktree_dir(dir)));
+        for dir in empty.into_iter().filter(|dir| dir != &root) {
+            problems.push(Problem::new(
+                format!("{} is an empty directory", dir.display()),
+                Some(Fix::RemoveDir {
+                    dir,
+                    root: root.clone(),
+                }),
+            ));
+        }
+    }
+
+    let mut repos: Vec<PathBuf> = registry.projects.values().map(|p| p.path.clone()).collect();
+    if let Ok(main) = git::main_worktree_root() {
+        repos.push(main);
+    }
+    repos.sort();
+    repos.dedup();
+    check_git_worktrees(&repos, &mut problems);
+    let mut sessions: BTreeSet<String> = managed.iter().map(Worktree::session_name).collect();
+    check_branches(&managed, &mut sessions, &mut problems);
+    check_leftovers(&managed, &sessions, &registry, &mut problems);
+    Ok(problems)
+}
+
+pub fn run(fix: bool) -> Result<()> {
+    let problems = diagnose()?;
+    if problems.is_empty() {
+        println!("No problems found.");
+        return Ok(());
+    }
+
+    let fixable = problems.iter().filter(|p| p.fix.is_some()).count();
+    println!("Found {} problem(s):", problems.len());
+    for problem in &problems {
+        println!("  - {}", problem.description);
+        match &problem.fix {
+            Some(f) if !fix => println!("    fix: {}", f.describe()),
+            Some(_) => {}
+            None => println!("    needs manual attention"),
+        }
+    }
+    if !fix {
+        bail!(
+            "Found {} problem(s); run `yati doctor --fix` to fix {} of them",
+            problems.len(),
+            fixable
+        );
+    }
+
+    println!();
+    let mut failed = 0;
+    let mut applied = Vec::new();
+    for f in problems.iter().filter_map(|p| p.fix.as_ref()) {
+        // Several problems may share a fix, e.g. pruning the same repository
+        let description = f.describe();
+        if applied.contains(&description) {
+            continue;
+        }
+        applied.push(description);
+        // Whatever runs in an orphan session may still be in use
+        if matches!(f, Fix::KillSession(..))
+            && !prune::confirm(&format!("Fix: {}?", f.describe()))?
+        {
+            println!("Skipped: {}", f.describe());
+            failed += 1;
+            continue;
+        }
+        match f.apply().with_context(|| format!("Failed to {}", f.describe())) {
+            Ok(()) => println!("Fixed: {}", f.describe()),
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                failed += 1;
+            }
+        }
+    }
+    let remaining = problems.len() - fixable + failed;
+    if remaining > 0 {
+        bail!("{} problem(s) still need attention", remaining);
+    }
+    Ok(())
+}
@@ -1,91 +1,299 @@
 use anyhow::{Context, Result};
-use std::path::Path;
+use serde::Serialize;
+use std::collections::HashMap;
 
-use crate::git;
+use crate::cli::ListArgs;
+use crate::metadata::Metadata;
+use crate::ports::{self, PortRegistry};
+use crate::worktrees::{self, Worktree};
+use crate::time::{format_age, now};
+use crate::{git, multiplexer, vars};
 
-pub fn run() -> Result<()> {
-    let yati_base = dirs::home_dir()
-        .context("Could not determine home directory")?
-        .join(".yati");
+/// Everything `yati list` knows about a worktree.
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub project: String,
+    pub branch: String,
+    pub session: String,
+    pub path: String,
+    pub head: String,
+    pub dirty: bool,
+    /// Commits ahead of / behind the upstream, if one is configured.
+    pub upstream: Option<AheadBehind>,
+    pub base: Option<String>,
+    /// Commits ahead of / behind the recorded base.
+    pub base_diff: Option<AheadBehind>,
+    pub session_alive: bool,
+    /// Unix timestamp of the last commit on the branch.
+    pub last_commit: Option<u64>,
+    /// Unix timestamp of the last activity in the session, if the multiplexer tracks it.
+    pub last_activity: Option<u64>,
+    /// Unix timestamp of the last `yati create` or `yati activate`.
+    pub last_activated: Option<u64>,
+    pub created: Option<u64>,
+    pub ports: Vec<u16>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Whether yati manages the worktree; only false with `yati list --all`.
+    pub managed: bool,
+}
 
-    if !yati_base.exists() {
-        println!("No yati-managed worktrees found.");
-        return Ok(());
+#[derive(Debug, Serialize)]
+pub struct AheadBehind {
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+impl AheadBehind {
+    fn between(worktree: &Worktree, left: &str, right: &str) -> Option<AheadBehind> {
+        git::ahead_behind(&worktree.path, left, right)
+            .map(|(ahead, behind)| AheadBehind { ahead, behind })
     }
+}
 
-    let mut projects: Vec<_> = std::fs::read_dir(&yati_base)
-        .context("Could not read ~/.yati directory")?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_dir())
-        .collect();
-    projects.sort_by_key(|e| e.file_name());
+impl Status {
+    pub fn collect(
+        worktree: &Worktree,
+        activity: &HashMap<String, Option<u64>>,
+        ports: &PortRegistry,
+        metadata: &Metadata,
+    ) -> Status {
+        let session = worktree.session_name();
+        let meta = metadata.get(worktree).cloned().unwrap_or_default();
+        let base = git::branch_base(&worktree.branch, &worktree.path);
+        Status {
+            project: worktree.project.clone(),
+            branch: worktree.branch.clone(),
+            path: worktree.path.to_string_lossy().to_string(),
+            head: git::head_short(&worktree.path).unwrap_or_else(|_| "???????".to_string()),
+            dirty: git::is_dirty(&worktree.path).unwrap_or(false),
+            upstream: AheadBehind::between(worktree, "HEAD", "@{upstream}"),
+            base_diff: base
+                .as_deref()
+                .and_then(|b| AheadBehind::between(worktree, "HEAD", b)),
+            base,
+            session_alive: activity.contains_key(&session),
+            last_commit: git::last_commit_time(&worktree.path),
+            last_activity: activity.get(&session).copied().flatten(),
+            last_activated: meta.last_activated,
+            created: meta.created,
+            ports: ports.get(&session).to_vec(),
+            description: meta.description,
+            tags: meta.tags,
+            managed: true,
+            session,
+        }
+    }
 
-    if projects.is_empty() {
-        println!("No yati-managed worktrees found.");
-        return Ok(());
+    /// The most recent of the last commit, the last session activity and the last
+    /// activation.
+    pub fn last_used(&self) -> Option<u64> {
+        self.last_commit
+            .max(self.last_activity)
+            .max(self.last_activated)
     }
 
-    let mut first = true;
-    for project in &projects {
-        let project_name = project.file_name();
-        let project_path = project.path();
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let diff = |d: &Option<AheadBehind>, f: fn(&AheadBehind) -> u32| {
+            d.as_ref().map(|d| f(d).to_string()).unwrap_or_default()
+        };
+        vec![
+            ("project", self.project.clone()),
+            ("branch", self.branch.clone()),
+            ("session", self.session.clone()),
+            ("path", self.path.clone()),
+            ("head", self.head.clone()),
+            (
+                "status",
+                if self.dirty { "dirty" } else { "clean" }.to_string(),
+            ),
+            ("ahead", diff(&self.upstream, |d| d.ahead)),
+            ("behind", diff(&self.upstream, |d| d.behind)),
+            ("base", self.base.clone().unwrap_or_default()),
+            ("base_ahead", diff(&self.base_diff, |d| d.ahead)),
+            ("base_behind", diff(&self.base_diff, |d| d.behind)),
+            (
+                "alive",
+                if self.session_alive { "yes" } else { "no" }.to_string(),
+            ),
+            (
+                "last_commit",
+                self.last_commit.map(format_age).unwrap_or_default(),
+            ),
+            (
+                "last_activity",
+                self.last_activity.map(format_age).unwrap_or_default(),
+            ),
+            ("ports", ports::join(&self.ports)),
+            ("created", self.created.map(format_age).unwrap_or_default()),
+            (
+                "last_activated",
+                self.last_activated.map(format_age).unwrap_or_default(),
+            ),
+            ("description", self.description.clone().unwrap_or_default()),
+            ("tags", self.tags.join(",")),
+            (
+                "managed",
+                if self.managed { "yes" } else { "no" }.to_string(),
+            ),
+        ]
+    }
 
-        let mut worktrees = find_worktrees(&project_path);
-        if worktrees.is_empty() {
-            continue;
-        }
-        worktrees.sort();
+    fn format(&self, template: &str) -> String {
+        let fields = self.fields();
+        vars::substitute(template, |name, _| {
+            fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, value)| value.clone())
+        })
+    }
+}
 
-        if !first {
-            println!();
-        }
-        first = false;
+fn format_diff(diff: &Option<AheadBehind>) -> String {
+    match diff {
+        Some(d) => format!("+{} -{}", d.ahead, d.behind),
+        None => "-".to_string(),
+    }
+}
 
-        println!("{}:", project_name.to_string_lossy());
-        for branch in &worktrees {
-            let wt_path = project_path.join(branch);
-            let short_head = git::head_short(&wt_path).unwrap_or_else(|_| "???????".to_string());
-            println!("  {} ({})", branch, short_head);
-        }
+/// Print the statuses as a table; `all` adds columns telling worktrees yati doesn't
+/// manage apart.
+fn print_table(statuses: &[Status], all: bool) {
+    let mut header = vec![
+        "WORKTREE", "HEAD", "STATUS", "UPSTREAM", "BASE", "SESSION", "COMMIT", "ACTIVE",
+        "PORTS", "TAGS", "DESCRIPTION",
+    ];
+    if all {
+        header.insert(1, "MANAGED");
+        header.push("PATH");
     }
+    let rows: Vec<Vec<String>> = statuses
+        .iter()
+        .map(|s| {
+            let mut row = vec![
+                s.session.clone(),
+                s.head.clone(),
+                if s.dirty { "dirty" } else { "clean" }.to_string(),
+                format_diff(&s.upstream),
+                match &s.base {
+                    Some(base) => format!("{} {}", base, format_diff(&s.base_diff)),
+                    None => "-".to_string(),
+                },
+                if s.session_alive { "alive" } else { "-" }.to_string(),
+                s.last_commit.map(format_age).unwrap_or_else(|| "-".to_string()),
+                s.last_activity
+                    .map(format_age)
+                    .unwrap_or_else(|| "-".to_string()),
+                ports::join(&s.ports),
+                s.tags.join(","),
+                s.description.clone().unwrap_or_default(),
+            ];
+            if all {
+                row.insert(1, if s.managed { "yes" } else { "-" }.to_string());
+                row.push(s.path.clone());
+            }
+            row
+        })
+        .collect();
 
-    if first {
-        println!("No yati-managed worktrees found.");
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let print_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(header);
+    for row in &rows {
+        print_row(row.iter().map(String::as_str).collect());
     }
+}
 
-    Ok(())
+/// Statuses of the worktrees that pass the filters in `args`.
+pub fn collect(worktrees: &[Worktree], ports: &PortRegistry, args: &ListArgs) -> Vec<Status> {
+    collect_with_managed(worktrees.iter().map(|w| (w, true)), ports, args)
 }
 
-/// Recursively find worktree directories under `base`.
-/// A worktree is identified by containing a `.git` file (not directory).
-/// Returns branch names relative to `base` (e.g. "main", "feature/foo").
-fn find_worktrees(base: &Path) -> Vec<String> {
-    let mut results = Vec::new();
-    find_worktrees_recursive(base, base, &mut results);
-    results
+/// Like `collect`, for worktrees paired with whether yati manages them.
+fn collect_with_managed<'a>(
+    worktrees: impl IntoIterator<Item = (&'a Worktree, bool)>,
+    ports: &PortRegistry,
+    args: &ListArgs,
+) -> Vec<Status> {
+    let activity = multiplexer::sessions();
+    let metadata = Metadata::load_all();
+    let stale_before = args.stale.map(|days| now().saturating_sub(days * 86400));
+
+    worktrees
+        .into_iter()
+        .filter(|(w, _)| args.project.as_ref().is_none_or(|p| &w.project == p))
+        .map(|(w, managed)| Status {
+            managed,
+            ..Status::collect(w, &activity, ports, &metadata)
+        })
+        .filter(|s| args.tag.as_ref().is_none_or(|t| s.tags.contains(t)))
+        .filter(|s| !args.dirty || s.dirty)
+        .filter(|s| !args.no_session || !s.session_alive)
+        .filter(|s| stale_before.is_none_or(|cutoff| s.last_used().is_none_or(|t| t < cutoff)))
+        .collect()
 }
 
-fn find_worktrees_recursive(base: &Path, current: &Path, results: &mut Vec<String>) {
-    let entries = match std::fs::read_dir(current) {
-        Ok(entries) => entries,
-        Err(_) => return,
+pub fn run(args: &ListArgs) -> Result<()> {
+    let ports = PortRegistry::load().unwrap_or_default();
+    let (all, statuses) = if args.all {
+        let everything = worktrees::discover_all()?;
+        let statuses = collect_with_managed(everything.iter().map(|(w, m)| (w, *m)), &ports, args);
+        let managed = everything
+            .into_iter()
+            .filter_map(|(w, managed)| managed.then_some(w))
+            .collect();
+        (managed, statuses)
+    } else {
+        let all = worktrees::discover()?;
+        let statuses = collect(&all, &ports, args);
+        (all, statuses)
     };
 
-    for entry in entries.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
+    if args.json {
+        let json =
+            serde_json::to_string_pretty(&statuses).context("Failed to serialize worktrees")?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    if let Some(template) = &args.format {
+        for status in &statuses {
+            println!("{}", status.format(template));
         }
+        return Ok(());
+    }
 
-        let git_path = path.join(".git");
-        if git_path.exists() && git_path.is_file() {
-            // This is a worktree leaf
-            if let Ok(rel) = path.strip_prefix(base) {
-                results.push(rel.to_string_lossy().to_string());
-            }
-        } else {
-            // Recurse to handle branch names with slashes
-            find_worktrees_recursive(base, &path, results);
+    if statuses.is_empty() {
+        println!("No yati-managed worktrees found.");
+    } else {
+        print_table(&statuses, args.all);
+    }
+
+    let orphaned: Vec<_> = ports
+        .allocations
+        .iter()
+        .filter(|(session, _)| !all.iter().any(|w| &w.session_name() == *session))
+        .collect();
+    if !orphaned.is_empty() {
+        println!();
+        println!("Ports held by missing worktrees:");
+        for (session, held) in orphaned {
+            println!("  {} ports {}", session, ports::join(held));
         }
     }
+
+    Ok(())
 }
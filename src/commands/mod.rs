@@ -1,5 +1,14 @@
 pub mod activate;
+pub mod adopt;
+pub mod config;
 pub mod create;
 pub mod deactivate;
+pub mod describe;
+pub mod doctor;
 pub mod list;
+pub mod prune;
+pub mod rename;
+pub mod restore;
+pub mod tag;
 pub mod teardown;
+pub mod trash;
@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::cli::PruneArgs;
+use crate::time::{format_age, now};
+use crate::commands::teardown;
+use crate::config::{self, Config};
+use crate::git::{self, MergeState};
+use crate::metadata::Metadata;
+use crate::worktrees::{self, Worktree};
+use crate::multiplexer;
+
+/// A worktree that can be pruned, and why.
+struct Candidate {
+    worktree: Worktree,
+    reasons: Vec<String>,
+    /// Whether the branch's changes are on a base, so it is safe to force-delete.
+    landed: bool,
+}
+
+/// Refs a branch counts as merged into: its recorded base, the project's
+/// `default_base`, and the repository's default branch.
+fn bases(worktree: &Worktree, config: &Config) -> Vec<String> {
+    let dir = &worktree.main_worktree;
+    let mut bases: Vec<String> = [
+        git::branch_base(&worktree.branch, dir),
+        config.default_base.clone(),
+        git::default_branch(dir),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|b| b != &worktree.branch)
+    .collect();
+    let mut seen = HashSet::new();
+    bases.retain(|b| seen.insert(b.clone()));
+    bases.retain(|b| git::resolve_commit(dir, b).is_some());
+    bases
+}
+
+fn check(
+    worktree: Worktree,
+    config: &Config,
+    activity: &HashMap<String, Option<u64>>,
+    metadata: &Metadata,
+    idle_before: Option<u64>,
+) -> Option<Candidate> {
+    let dir = &worktree.main_worktree;
+    let branch = &worktree.branch;
+    let mut reasons = Vec::new();
+    let mut landed = false;
+
+    // A branch that never moved from its base is trivially "merged" into it; that's new
+    // work, not finished work
+    let bases = bases(&worktree, config);
+    if git::branch_has_moved(dir, branch, &bases) {
+        for base in &bases {
+            match git::merge_state(dir, branch, base) {
+                Some(MergeState::Merged) => reasons.push(format!("merged into {}", base)),
+                Some(MergeState::Squashed) => {
+                    reasons.push(format!("squash-merged into {}", base))
+                }
+                None => continue,
+            }
+            landed = true;
+            break;
+        }
+    }
+
+    if git::upstream_gone(dir, branch) {
+        reasons.push("remote branch gone".to_string());
+    }
+
+    if let Some(cutoff) = idle_before {
+        let last_used = git::last_commit_time(&worktree.path)
+            .max(activity.get(&worktree.session_name()).copied().flatten())
+            .max(metadata.get(&worktree).and_then(|m| m.last_activated));
+        if last_used.is_none_or(|t| t < cutoff) {
+            reasons.push(match last_used {
+                Some(t) => format!("idle for {}", format_age(t)),
+                None => "idle".to_string(),
+            });
+        }
+    }
+
+    (!reasons.is_empty()).then_some(Candidate {
+        worktree,
+        reasons,
+        landed,
+    })
+}
+
+/// Ask a yes/no question on stdin, defaulting to no.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush().ok();
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read confirmation")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub fn run(args: &PruneArgs) -> Result<()> {
+    let all: Vec<Worktree> = worktrees::discover()?
+        .into_iter()
+        .filter(|w| args.project.as_ref().is_none_or(|p| &w.project == p))
+        .collect();
+
+    let mut configs: HashMap<PathBuf, Config> = HashMap::new();
+    for worktree in &all {
+        if configs.contains_key(&worktree.main_worktree) {
+            continue;
+        }
+        if args.fetch {
+            println!("Fetching {}...", worktree.project);
+            if let Err(e) = git::fetch_prune(&worktree.main_worktree) {
+                eprintln!("Warning: failed to fetch {}: {}", worktree.project, e);
+            }
+        }
+        let config = config::load_config(&worktree.main_worktree)?;
+        configs.insert(worktree.main_worktree.clone(), config);
+    }
+
+    let activity = multiplexer::sessions();
+    let metadata = Metadata::load_all();
+    let idle_before = args.idle.map(|days| now().saturating_sub(days * 86400));
+    let candidates: Vec<Candidate> = all
+        .into_iter()
+        .filter_map(|w| {
+            let config = &configs[&w.main_worktree];
+            check(w, config, &activity, &metadata, idle_before)
+        })
+        .collect();
+
+    if candidates.is_empty() {
+        println!("Nothing to prune.");
+        return Ok(());
+    }
+
+    let width = candidates
+        .iter()
+        .map(|c| c.worktree.session_name().len())
+        .max()
+        .unwrap_or(0);
+    for candidate in &candidates {
+        println!(
+            "  {:<width$}  {}",
+            candidate.worktree.session_name(),
+            candidate.reasons.join(", "),
+            width = width
+        );
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+    let prompt = format!("Tear down {} worktree(s)?", candidates.len());
+    if !args.yes && !confirm(&prompt)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    teardown::teardown_all(
+        candidates
+            .into_iter()
+            .map(|Candidate { worktree, landed, .. }| {
+                let options = teardown::Options {
+                    force: args.force,
+                    // Squash-merged branches aren't merged as far as `git branch -d` is
+                    // concerned
+                    force_branch: landed,
+                    push: args.push,
+                    archive: args.archive,
+                    ..Default::default()
+                };
+                (worktree, options)
+            })
+            .collect(),
+    )
+}
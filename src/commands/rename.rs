@@ -0,0 +1,79 @@
+use anyhow::{bail, Context, Result};
+
+use crate::commands::activate::parse_target;
+use crate::worktrees::Worktree;
+use crate::{config, git, metadata, multiplexer, paths, ports};
+
+pub fn run(target: &str, new_branch: &str) -> Result<()> {
+    let worktree = parse_target(target)?;
+    git::validate_branch_name(new_branch)?;
+    if new_branch == worktree.branch {
+        bail!("'{}' is already named '{}'", worktree.session_name(), new_branch);
+    }
+    let main_worktree = &worktree.main_worktree;
+    if git::resolve_commit(main_worktree, &format!("refs/heads/{}", new_branch)).is_some() {
+        bail!("Branch '{}' already exists", new_branch);
+    }
+
+    let config = config::load_config(main_worktree)?;
+    // Adopted worktrees outside the layout stay where they are
+    let in_layout = paths::branch_from_path(&config, &worktree.project, &worktree.path)?
+        .is_some_and(|b| b == worktree.branch);
+    let new_path = if in_layout {
+        paths::worktree_path(&config, &worktree.project, new_branch)?
+    } else {
+        worktree.path.clone()
+    };
+    if in_layout && new_path.exists() {
+        bail!("Worktree path already exists: {}", new_path.display());
+    }
+    let renamed = Worktree {
+        project: worktree.project.clone(),
+        branch: new_branch.to_string(),
+        path: new_path.clone(),
+        main_worktree: main_worktree.clone(),
+    };
+    let old_session = worktree.session_name();
+    let new_session = renamed.session_name();
+    let mux = multiplexer::for_config(&config);
+    if let Some(mux) = &mux
+        && mux.session_exists(&new_session)
+    {
+        bail!("A {} session named '{}' already exists", mux.name(), new_session);
+    }
+
+    // Renaming the branch also moves its config, including the recorded base
+    git::branch_rename(&worktree.branch, new_branch, main_worktree)?;
+    if in_layout {
+        if let Some(parent) = new_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        if let Err(e) = git::worktree_move(&worktree.path, &new_path, main_worktree) {
+            if let Err(undo) = git::branch_rename(new_branch, &worktree.branch, main_worktree) {
+                eprintln!("Warning: failed to rename the branch back: {}", undo);
+            }
+            return Err(e);
+        }
+        if let Some(parent) = worktree.path.parent() {
+            paths::cleanup_empty_parents(parent, &paths::project_root(&config)?);
+        }
+    }
+
+    if let Some(mux) = &mux
+        && mux.session_exists(&old_session)
+    {
+        mux.rename_session(&old_session, &new_session)?;
+    }
+    ports::rename(&old_session, &new_session)?;
+    metadata::rename(&worktree, new_branch, (!in_layout).then(|| new_path.clone()))?;
+
+    println!("Renamed '{}' to '{}'", old_session, new_session);
+    if in_layout {
+        println!("Worktree moved to {}", new_path.display());
+        if mux.as_ref().is_some_and(|mux| mux.session_exists(&new_session)) {
+            println!("Shells already open in the session are still in the old directory.");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anyhow::{bail, Result};
+
+use crate::commands::activate;
+use crate::git::{self, BranchStart};
+use crate::trash::{self, Entry};
+use crate::{config, paths};
+
+/// The trash entry `target` refers to: `project/branch`, or a branch of the current
+/// project. The most recent one unless `at` picks a timestamp.
+fn find(target: &str, at: Option<u64>) -> Result<Entry> {
+    let current = git::main_worktree_root().ok();
+    let entry = trash::all_entries()
+        .into_iter()
+        .filter(|e| {
+            e.target() == target
+                || (e.branch == target && current.as_ref() == Some(&e.main_worktree))
+        })
+        .filter(|e| at.is_none_or(|t| e.timestamp == t))
+        .max_by_key(|e| e.timestamp);
+    match entry {
+        Some(entry) => Ok(entry),
+        None => bail!(
+            "No trashed worktree found for '{}' (see `yati trash list`)",
+            target
+        ),
+    }
+}
+
+pub fn run(target: &str, at: Option<u64>) -> Result<()> {
+    let entry = find(target, at)?;
+    let main_worktree = &entry.main_worktree;
+    let branch = &entry.branch;
+    let config = config::load_config(main_worktree)?;
+    let path = paths::worktree_path(&config, &entry.project, branch)?;
+    if path.exists() {
+        bail!("Worktree path already exists: {}", path.display());
+    }
+    // The branch is still there if `teardown.delete_branch = "never"` kept it
+    let trashed = git::resolve_commit(main_worktree, &format!("{}^", entry.refname));
+    let existing = git::resolve_commit(main_worktree, &format!("refs/heads/{}", branch));
+    if existing.is_some() && existing != trashed {
+        bail!(
+            "Branch '{}' already exists; rename or delete it before restoring",
+            branch
+        );
+    }
+
+    println!("Restoring worktree at {}", path.display());
+    if existing.is_none() {
+        git::branch_create(branch, &format!("{}^", entry.refname), main_worktree)?;
+    }
+    git::worktree_add(&path, branch, &BranchStart::Existing, main_worktree)?;
+    if entry.has_changes() {
+        git::restore_worktree_files(&path, &entry.refname)?;
+    }
+    trash::extract_ignored(&entry, &path)?;
+    if let Some(base) = entry.base() {
+        git::set_branch_base(branch, &base, main_worktree)?;
+    }
+    trash::remove(&entry)?;
+
+    activate::activate(activate::parse_target(&entry.target())?, false, true)
+}
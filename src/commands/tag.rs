@@ -0,0 +1,30 @@
+use anyhow::Result;
+
+use crate::commands::activate::parse_target;
+use crate::metadata;
+
+/// Add `tags` to `target` (or remove them with `remove`), then print its tags.
+pub fn run(target: &str, tags: &[String], remove: bool) -> Result<()> {
+    let worktree = parse_target(target)?;
+    if !tags.is_empty() {
+        metadata::update(&worktree, |meta| {
+            if remove {
+                meta.tags.retain(|t| !tags.contains(t));
+            } else {
+                for tag in tags {
+                    if !meta.tags.contains(tag) {
+                        meta.tags.push(tag.clone());
+                    }
+                }
+            }
+        })?;
+    }
+
+    let current = metadata::get(&worktree)?.tags;
+    if current.is_empty() {
+        println!("'{}' has no tags", worktree.session_name());
+    } else {
+        println!("{}: {}", worktree.session_name(), current.join(", "));
+    }
+    Ok(())
+}
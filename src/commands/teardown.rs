@@ -1,98 +1,652 @@
 use anyhow::{bail, Context, Result};
-use std::path::PathBuf;
-use std::process::Command;
-
-use crate::{config, git, tmux};
-
-pub fn run(force: bool) -> Result<()> {
-    let cwd = std::env::current_dir().context("Failed to get current directory")?;
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-    let yati_base = home.join(".yati");
-
-    let relative = cwd
-        .strip_prefix(&yati_base)
-        .map_err(|_| anyhow::anyhow!("Not in a yati worktree (current directory is not under ~/.yati/)"))?;
-
-    // Extract project and branch from path: <project>/<branch>/...
-    let components: Vec<_> = relative.components().collect();
-    if components.len() < 2 {
-        bail!("Cannot determine project/branch from path: {}", cwd.display());
-    }
-    let project = components[0].as_os_str().to_string_lossy().to_string();
-    let branch = components[1].as_os_str().to_string_lossy().to_string();
-    let worktree_path = yati_base.join(&project).join(&branch);
-
-    // Find main worktree to load config from
-    let entries = git::worktree_list()?;
-    let main_worktree = entries
-        .first()
-        .context("No worktrees found")?;
-    let config = config::load_config(&main_worktree.path)?;
-
-    // Run pre_teardown hooks
-    for hook in &config.pre_teardown {
-        println!("Running pre_teardown hook: {}", hook);
-        let status = Command::new("sh")
-            .args(["-c", hook])
-            .current_dir(&worktree_path)
-            .status()
-            .with_context(|| format!("Failed to run hook: {}", hook))?;
-        if !status.success() {
-            eprintln!("Warning: pre_teardown hook failed: {}", hook);
+use std::path::{Path, PathBuf};
+
+use crate::commands::activate;
+use crate::time::now;
+use crate::ports::{self, PortRegistry};
+use crate::vars::Vars;
+use crate::worktrees::{self, Worktree};
+use crate::config::{Config, DeleteBranch};
+use crate::multiplexer::{self, Multiplexer};
+use crate::{config, git, hooks, metadata, paths, trash};
+
+/// Ignored files bigger than this are called out by `--dry-run`.
+const LARGE_FILE: u64 = 50 * 1024 * 1024;
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Remove the worktree even with uncommitted changes, and delete unmerged branches.
+    pub force: bool,
+    /// Use `git branch -D` even if git doesn't consider the branch merged (e.g.
+    /// squash-merged), when `teardown.delete_branch` allows deleting it at all.
+    pub force_branch: bool,
+    /// Push unpushed commits before deleting the branch.
+    pub push: bool,
+    /// Keep unpushed commits under `refs/yati/archive/` before deleting the branch.
+    pub archive: bool,
+    /// Move the branch and any uncommitted changes to the trash instead of discarding them.
+    pub trash: bool,
+    /// With `trash`, also keep the ignored files listed in `copy_files`.
+    pub with_ignored: bool,
+    /// Only print what would be done.
+    pub dry_run: bool,
+    /// Keep the branch, whatever `teardown.delete_branch` says.
+    pub keep_branch: bool,
+}
+
+/// What happens to the branch of a torn-down worktree.
+enum BranchAction {
+    Keep(&'static str),
+    /// Delete it; `force` uses `git branch -D` rather than `-d`. `safe` means its commits
+    /// are known to be kept elsewhere (trash, or merged into the base), so there is no
+    /// need to check for unpushed ones.
+    Delete { force: bool, safe: bool },
+}
+
+/// Decide what to do with the branch, from `options` and `teardown.delete_branch`.
+fn branch_action(
+    worktree: &Worktree,
+    options: &Options,
+    config: &Config,
+    base_ref: Option<&str>,
+) -> BranchAction {
+    if options.keep_branch {
+        return BranchAction::Keep("--keep-branch");
+    }
+    match config.teardown.delete_branch {
+        Some(DeleteBranch::Never) => BranchAction::Keep("teardown.delete_branch = \"never\""),
+        // The trash holds on to the branch
+        _ if options.trash => BranchAction::Delete {
+            force: true,
+            safe: true,
+        },
+        Some(DeleteBranch::Always) => BranchAction::Delete {
+            force: true,
+            safe: false,
+        },
+        Some(DeleteBranch::IfMerged) => {
+            let merged = base_ref.is_some_and(|base| {
+                git::merge_state(&worktree.main_worktree, &worktree.branch, base).is_some()
+            });
+            if merged {
+                BranchAction::Delete {
+                    force: true,
+                    safe: true,
+                }
+            } else {
+                BranchAction::Keep("not merged into its base")
+            }
         }
+        // Prune only forces branches that landed on a base
+        None => BranchAction::Delete {
+            force: options.force || options.archive || options.force_branch,
+            safe: options.force_branch,
+        },
     }
+}
+
+/// Tear down the worktrees named by `targets`, or the current worktree if none are given.
+pub fn run(targets: &[String], options: Options) -> Result<()> {
+    let mut worktrees = if targets.is_empty() {
+        vec![worktrees::current()?]
+    } else {
+        targets
+            .iter()
+            .map(|t| activate::parse_target(t))
+            .collect::<Result<Vec<_>>>()?
+    };
+    worktrees.sort_by(|a, b| a.path.cmp(&b.path));
+    worktrees.dedup_by(|a, b| a.path == b.path);
+
+    teardown_all(
+        worktrees
+            .into_iter()
+            .map(|w| (w, options.clone()))
+            .collect(),
+    )
+}
 
-    let session_name = format!("{}/{}", project, branch);
+/// Tear down each worktree with its options, the one whose session we are running in
+/// last. Keeps going past failures and reports how many there were; a single worktree's
+/// error is returned as is.
+pub fn teardown_all(mut worktrees: Vec<(Worktree, Options)>) -> Result<()> {
+    // Tearing down the session we are running in kills this process, so do it last
+    let current = multiplexer::current_session();
+    worktrees.sort_by_key(|(w, _)| current.as_deref() == Some(w.session_name().as_str()));
 
-    // Kill all other panes/windows in the session to stop processes (e.g. neovim, LSP)
-    // that could write files back during cleanup. Keep our pane alive.
-    if tmux::is_in_tmux() {
-        tmux::kill_other_panes(&session_name)?;
+    let mut failed = 0;
+    for (worktree, options) in &worktrees {
+        if let Err(e) = teardown(worktree, options) {
+            if worktrees.len() == 1 {
+                return Err(e);
+            }
+            eprintln!("Error: failed to tear down '{}': {:#}", worktree.session_name(), e);
+            failed += 1;
+        }
     }
+    if failed > 0 {
+        bail!("Failed to tear down {} of {} worktrees", failed, worktrees.len());
+    }
+    Ok(())
+}
 
-    println!("Removing worktree at {}", worktree_path.display());
-    git::worktree_remove(&worktree_path, force, &main_worktree.path)?;
+/// Run the `pre_teardown` hooks, then remove the worktree, its branch, ports and
+/// session. Only switches or detaches the client if it is in the worktree's session.
+/// With `dry_run`, the same steps are only printed.
+pub fn teardown(worktree: &Worktree, options: &Options) -> Result<()> {
+    let teardown = Teardown::new(worktree, options)?;
+    let actions = plan(&teardown)?;
 
-    // Remove the worktree directory if it still exists
-    if worktree_path.exists() {
-        std::fs::remove_dir_all(&worktree_path)
-            .with_context(|| format!("Failed to remove directory {}", worktree_path.display()))?;
+    if options.dry_run {
+        println!(
+            "Dry run for '{}', nothing will be changed:",
+            teardown.session_name
+        );
+        for action in &actions {
+            action.print(&teardown)?;
+        }
+        // Fail like the real run would, so a dry run can be used as a check
+        if matches!(actions.last(), Some(Action::Stop { .. })) {
+            bail!("Teardown of '{}' would stop here", teardown.session_name);
+        }
+        if teardown.in_session {
+            println!(
+                "  - Switch to the previous session (or detach) and kill session '{}'",
+                teardown.session_name
+            );
+        }
+        return Ok(());
     }
 
-    // Prune stale worktree metadata
-    if let Err(e) = git::worktree_prune(&main_worktree.path) {
-        eprintln!("Warning: failed to prune worktrees: {}", e);
+    for action in &actions {
+        action.run(&teardown)?;
     }
+    println!("Worktree '{}' removed successfully", teardown.session_name);
 
-    // Delete the branch
-    if let Err(e) = git::branch_delete(&branch, force, &main_worktree.path) {
-        eprintln!("Warning: failed to delete branch '{}': {}", branch, e);
+    // Now switch away and kill the session we are running in
+    if teardown.in_session
+        && let Some(mux) = &teardown.mux
+    {
+        println!("Killing {} session '{}'...", mux.name(), teardown.session_name);
+        if !mux.switch_to_previous_session() {
+            mux.detach()?;
+        }
+        mux.kill_session(&teardown.session_name)?;
     }
 
-    // Clean up empty parent directories
-    let project_dir = yati_base.join(&project);
-    cleanup_empty_dir(&project_dir);
-    cleanup_empty_dir(&yati_base);
+    Ok(())
+}
 
-    // Now switch away and kill the session
-    if tmux::is_in_tmux() {
-        println!("Killing tmux session '{}'...", session_name);
-        if !tmux::switch_to_previous_session() {
-            tmux::detach()?;
+/// Everything the steps of a teardown need to know about the worktree.
+struct Teardown<'a> {
+    worktree: &'a Worktree,
+    options: &'a Options,
+    session_name: String,
+    base_ref: Option<String>,
+    reserved: Vec<u16>,
+    config: Config,
+    vars: Vars,
+    mux: Option<Box<dyn Multiplexer>>,
+    /// Whether this process runs in the worktree's own session.
+    in_session: bool,
+}
+
+impl<'a> Teardown<'a> {
+    fn new(worktree: &'a Worktree, options: &'a Options) -> Result<Teardown<'a>> {
+        let base_ref = git::branch_base(&worktree.branch, &worktree.main_worktree);
+        let session_name = worktree.session_name();
+        let reserved = PortRegistry::load()?.get(&session_name).to_vec();
+        let config = config::load_config(&worktree.main_worktree)?;
+        let vars = Vars::new(worktree, base_ref.as_deref()).with_ports(&reserved);
+        let config = vars.apply(&config);
+        let vars = vars.with_env(&config.env);
+        let mux = multiplexer::for_config(&config);
+        let in_session = mux
+            .as_ref()
+            .is_some_and(|mux| mux.current_session().as_deref() == Some(session_name.as_str()));
+        Ok(Teardown {
+            worktree,
+            options,
+            session_name,
+            base_ref,
+            reserved,
+            config,
+            vars,
+            mux,
+            in_session,
+        })
+    }
+}
+
+/// One step of a teardown, printed by `--dry-run` and run otherwise.
+enum Action {
+    /// Save the branch and its changes to the trash, with these ignored files.
+    Trash(Vec<String>),
+    /// Push these unpushed commits before the branch is deleted.
+    Push(Vec<String>),
+    /// Keep these unpushed commits under `refs/yati/archive/`.
+    Archive(Vec<String>),
+    /// Let these unpushed commits go with the branch (`--force`).
+    Discard(Vec<String>),
+    /// Refuse to go on, listing `details`.
+    Stop {
+        reason: String,
+        details: Vec<String>,
+        hint: &'static str,
+    },
+    Hook(String),
+    /// Kill the session's panes other than the one running yati.
+    KillOtherPanes,
+    KillSession,
+    RemoveWorktree,
+    PruneWorktrees,
+    Branch(BranchAction),
+    ReleasePorts,
+    ForgetMetadata,
+    RemoveEmptyParents,
+}
+
+/// The steps tearing down the worktree takes, in order. A plan that can't be
+/// carried out ends in `Action::Stop`.
+fn plan(teardown: &Teardown) -> Result<Vec<Action>> {
+    let Teardown {
+        worktree, options, ..
+    } = teardown;
+    let path = &worktree.path;
+    let mut actions = Vec::new();
+    let branch_action =
+        branch_action(worktree, options, &teardown.config, teardown.base_ref.as_deref());
+
+    if teardown.in_session
+        && let Some(mux) = &teardown.mux
+        && !mux.can_kill_other_panes()
+    {
+        actions.push(Action::Stop {
+            reason: format!(
+                "{} can't close the other panes of '{}' while you are in it",
+                mux.name(),
+                teardown.session_name
+            ),
+            details: Vec::new(),
+            hint: "Run the teardown from outside the session",
+        });
+        return Ok(actions);
+    }
+
+    if !(options.force || options.trash) && path.exists() && git::is_dirty(path)? {
+        actions.push(Action::Stop {
+            reason: format!(
+                "'{}' has uncommitted changes or untracked files",
+                teardown.session_name
+            ),
+            details: Vec::new(),
+            hint: "Use --trash to keep them, or --force to discard them",
+        });
+        return Ok(actions);
+    }
+
+    if options.trash {
+        let ignored = if options.with_ignored {
+            teardown
+                .config
+                .copy_files
+                .iter()
+                .filter(|file| path.join(file).exists() && git::is_ignored(path, file))
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+        actions.push(Action::Trash(ignored));
+    } else if matches!(branch_action, BranchAction::Delete { safe: false, .. }) {
+        let unpushed = git::unpushed_commits(
+            &worktree.main_worktree,
+            &worktree.branch,
+            teardown.base_ref.as_deref(),
+        )?;
+        if !unpushed.is_empty() {
+            if options.push {
+                actions.push(Action::Push(unpushed));
+            } else if options.archive {
+                actions.push(Action::Archive(unpushed));
+            } else if options.force {
+                actions.push(Action::Discard(unpushed));
+            } else {
+                actions.push(Action::Stop {
+                    reason: format!(
+                        "'{}' has {} commit(s) that aren't on any remote{}",
+                        teardown.session_name,
+                        unpushed.len(),
+                        teardown
+                            .base_ref
+                            .as_ref()
+                            .map(|b| format!(" or {}", b))
+                            .unwrap_or_default()
+                    ),
+                    details: unpushed,
+                    hint: "Use --push to push them, --archive to keep a backup ref, or --force to discard them",
+                });
+                return Ok(actions);
+            }
         }
-        tmux::kill_session(&session_name)?;
     }
 
-    println!("Worktree '{}' removed successfully", branch);
-    Ok(())
+    for hook in &teardown.config.pre_teardown {
+        actions.push(Action::Hook(hook.clone()));
+    }
+
+    // Stop processes (e.g. neovim, LSP) that could write files back during cleanup.
+    // If we are running inside the session, kill everything but our own pane.
+    if let Some(mux) = &teardown.mux {
+        if teardown.in_session {
+            actions.push(Action::KillOtherPanes);
+        } else if mux.session_exists(&teardown.session_name) {
+            actions.push(Action::KillSession);
+        }
+    }
+
+    actions.push(Action::RemoveWorktree);
+    actions.push(Action::PruneWorktrees);
+    actions.push(Action::Branch(branch_action));
+    actions.push(Action::ReleasePorts);
+    actions.push(Action::ForgetMetadata);
+    actions.push(Action::RemoveEmptyParents);
+    Ok(actions)
 }
 
-fn cleanup_empty_dir(path: &PathBuf) {
-    if path.is_dir() {
-        if let Ok(mut entries) = std::fs::read_dir(path) {
-            if entries.next().is_none() {
-                let _ = std::fs::remove_dir(path);
+impl Action {
+    /// Describe the step for `--dry-run`.
+    fn print(&self, teardown: &Teardown) -> Result<()> {
+        let worktree = teardown.worktree;
+        let branch = &worktree.branch;
+        let path = &worktree.path;
+        let print_commits = |commits: &[String]| {
+            for commit in commits {
+                println!("      {}", commit);
+            }
+        };
+        match self {
+            Action::Trash(ignored) => {
+                println!(
+                    "  - Save '{}' and its uncommitted changes as refs/yati/trash/{}/{}/<timestamp>",
+                    branch, worktree.project, branch
+                );
+                for file in ignored {
+                    println!("  - Archive ignored {}", path.join(file).display());
+                }
+            }
+            Action::Push(commits) => {
+                println!("  - Push {} unpushed commit(s) of '{}':", commits.len(), branch);
+                print_commits(commits);
+            }
+            Action::Archive(commits) => {
+                println!(
+                    "  - Keep {} unpushed commit(s) as refs/yati/archive/{}/<timestamp>:",
+                    commits.len(),
+                    branch
+                );
+                print_commits(commits);
+            }
+            Action::Discard(commits) => {
+                println!("  - Discard {} unpushed commit(s):", commits.len());
+                print_commits(commits);
+            }
+            Action::Stop {
+                reason,
+                details,
+                hint,
+            } => {
+                println!("  - Stop: {}", reason);
+                print_commits(details);
+                println!("      {}", hint);
+            }
+            Action::Hook(hook) => {
+                println!("  - Run pre_teardown hook in {}: {}", path.display(), hook)
+            }
+            Action::KillOtherPanes => {
+                let Some(mux) = &teardown.mux else {
+                    return Ok(());
+                };
+                let current = mux.current_pane();
+                for (id, description) in mux.panes(&teardown.session_name)? {
+                    if current.as_ref() != Some(&id) {
+                        println!("  - Kill pane {} ({})", id, description);
+                    }
+                }
+            }
+            Action::KillSession => {
+                let Some(mux) = &teardown.mux else {
+                    return Ok(());
+                };
+                println!(
+                    "  - Kill {} session '{}' and its panes:",
+                    mux.name(),
+                    teardown.session_name
+                );
+                for (id, description) in mux.panes(&teardown.session_name)? {
+                    println!("      {} ({})", id, description);
+                }
+            }
+            Action::RemoveWorktree => {
+                print_ignored(path, &teardown.config.copy_files)?;
+                println!("  - Remove worktree at {}", path.display());
+            }
+            Action::PruneWorktrees => println!(
+                "  - Prune worktree metadata of {}",
+                worktree.main_worktree.display()
+            ),
+            Action::Branch(BranchAction::Keep(reason)) => {
+                println!("  - Keep branch '{}' ({})", branch, reason)
+            }
+            Action::Branch(BranchAction::Delete { force, .. }) => println!(
+                "  - Delete branch '{}' (git branch {})",
+                branch,
+                if *force { "-D" } else { "-d" }
+            ),
+            Action::ReleasePorts => {
+                if !teardown.reserved.is_empty() {
+                    println!("  - Release ports {}", ports::join(&teardown.reserved));
+                }
+            }
+            Action::ForgetMetadata => {}
+            Action::RemoveEmptyParents => {
+                for dir in emptied_parents(path, &paths::project_root(&teardown.config)?) {
+                    println!("  - Remove empty directory {}", dir.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&self, teardown: &Teardown) -> Result<()> {
+        let Teardown {
+            worktree, options, ..
+        } = teardown;
+        let branch = &worktree.branch;
+        let path = &worktree.path;
+        let main_worktree = &worktree.main_worktree;
+        match self {
+            Action::Trash(ignored) => {
+                let entry = trash::save(worktree, teardown.base_ref.as_deref(), ignored)?;
+                println!(
+                    "Moved '{}' to the trash as {} (bring it back with `yati restore {}`)",
+                    teardown.session_name,
+                    entry.refname,
+                    entry.target()
+                );
+            }
+            Action::Push(_) => git::push_branch(main_worktree, branch)?,
+            Action::Archive(_) => {
+                let refname = format!("refs/yati/archive/{}/{}", branch, now());
+                git::update_ref(main_worktree, &refname, &format!("refs/heads/{}", branch))?;
+                println!(
+                    "Archived '{}' as {} (restore with `git branch {} {}`)",
+                    branch, refname, branch, refname
+                );
+            }
+            Action::Discard(_) => {}
+            Action::Stop {
+                reason,
+                details,
+                hint,
+            } => {
+                let mut message = reason.clone();
+                if !details.is_empty() {
+                    message.push(':');
+                }
+                for detail in details {
+                    message.push_str(&format!("\n  {}", detail));
+                }
+                bail!("{}\n{}", message, hint)
+            }
+            Action::Hook(hook) => {
+                hooks::run("pre_teardown", std::slice::from_ref(hook), path, &teardown.vars)?
+            }
+            Action::KillOtherPanes => {
+                if let Some(mux) = &teardown.mux {
+                    mux.kill_other_panes(&teardown.session_name)?;
+                }
+            }
+            Action::KillSession => {
+                if let Some(mux) = &teardown.mux {
+                    println!("Killing {} session '{}'...", mux.name(), teardown.session_name);
+                    mux.kill_session(&teardown.session_name)?;
+                }
+            }
+            Action::RemoveWorktree => {
+                println!("Removing worktree at {}", path.display());
+                git::worktree_remove(path, options.force || options.trash, main_worktree)?;
+                // Remove the worktree directory if it still exists
+                if path.exists() {
+                    std::fs::remove_dir_all(path).with_context(|| {
+                        format!("Failed to remove directory {}", path.display())
+                    })?;
+                }
+            }
+            Action::PruneWorktrees => {
+                if let Err(e) = git::worktree_prune(main_worktree) {
+                    eprintln!("Warning: failed to prune worktrees: {}", e);
+                }
+            }
+            Action::Branch(BranchAction::Keep(reason)) => {
+                println!("Keeping branch '{}' ({})", branch, reason)
+            }
+            Action::Branch(BranchAction::Delete { force, .. }) => {
+                if let Err(e) = git::branch_delete(branch, *force, main_worktree) {
+                    eprintln!("Warning: failed to delete branch '{}': {}", branch, e);
+                }
+            }
+            Action::ReleasePorts => {
+                if let Err(e) = ports::release(&teardown.session_name) {
+                    eprintln!("Warning: failed to release ports: {}", e);
+                }
+            }
+            Action::ForgetMetadata => {
+                if let Err(e) = metadata::remove(worktree) {
+                    eprintln!("Warning: failed to remove worktree metadata: {}", e);
+                }
+            }
+            Action::RemoveEmptyParents => {
+                if let Some(parent) = path.parent() {
+                    paths::cleanup_empty_parents(parent, &paths::project_root(&teardown.config)?);
+                }
             }
         }
+        Ok(())
+    }
+}
+
+/// List the ignored files removing the worktree deletes, calling out large ones and
+/// ones yati didn't copy in.
+fn print_ignored(path: &Path, copy_files: &[String]) -> Result<()> {
+    let ignored = git::ignored_files(path)?;
+    if ignored.is_empty() {
+        return Ok(());
+    }
+    println!("  - Delete ignored files (git clean -fdX):");
+    let mut not_copied = 0;
+    for file in &ignored {
+        let size = disk_usage(&path.join(file));
+        let mut notes = vec![format_size(size)];
+        if size >= LARGE_FILE {
+            notes.push("large".to_string());
+        }
+        if !copied_by_yati(file, copy_files) {
+            notes.push("not copied in by yati".to_string());
+            not_copied += 1;
+        }
+        println!("      {} ({})", path.join(file).display(), notes.join(", "));
+    }
+    if not_copied > 0 {
+        eprintln!(
+            "Warning: {} ignored file(s) were not copied in by yati and will be lost",
+            not_copied
+        );
+    }
+    Ok(())
+}
+
+/// Whether `file` (as listed by `git clean`) is, or is inside, one of `copy_files`.
+fn copied_by_yati(file: &str, copy_files: &[String]) -> bool {
+    let file = file.trim_end_matches('/');
+    copy_files.iter().any(|entry| {
+        let entry = entry.trim_end_matches('/');
+        file == entry || file.starts_with(&format!("{}/", entry))
+    })
+}
+
+/// Total size of `path` and everything under it, not following symlinks.
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| disk_usage(&e.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Directories between `path` and `root` that would be left empty once `path` is
+/// removed, innermost first, as `paths::cleanup_empty_parents` would remove them.
+fn emptied_parents(path: &Path, root: &Path) -> Vec<PathBuf> {
+    let mut emptied = Vec::new();
+    let mut removed = path.to_path_buf();
+    while let Some(dir) = removed.parent() {
+        if !dir.starts_with(root) {
+            break;
+        }
+        let only_child = std::fs::read_dir(dir)
+            .is_ok_and(|mut entries| entries.all(|e| e.is_ok_and(|e| e.path() == removed)));
+        if !only_child {
+            break;
+        }
+        emptied.push(dir.to_path_buf());
+        if dir == root {
+            break;
+        }
+        removed = dir.to_path_buf();
     }
+    emptied
 }
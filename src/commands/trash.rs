@@ -0,0 +1,73 @@
+use anyhow::{bail, Result};
+
+use crate::time::{format_age, now};
+use crate::commands::prune::confirm;
+use crate::trash::{self, Entry};
+
+fn describe(entry: &Entry) -> String {
+    let mut contents = vec!["commits"];
+    if entry.has_changes() {
+        contents.push("uncommitted changes");
+    }
+    if entry.tarball().is_ok_and(|t| t.exists()) {
+        contents.push("ignored files");
+    }
+    contents.join(", ")
+}
+
+fn print_entries(entries: &[Entry]) {
+    let width = entries.iter().map(|e| e.target().len()).max().unwrap_or(0);
+    for entry in entries {
+        println!(
+            "  {:<width$}  {}  {:>4} ago  {}",
+            entry.target(),
+            entry.timestamp,
+            format_age(entry.timestamp),
+            describe(entry),
+            width = width
+        );
+    }
+}
+
+pub fn list() -> Result<()> {
+    let entries = trash::all_entries();
+    if entries.is_empty() {
+        println!("Trash is empty.");
+    } else {
+        print_entries(&entries);
+    }
+    Ok(())
+}
+
+pub fn purge(targets: &[String], older_than: Option<u64>, yes: bool) -> Result<()> {
+    let cutoff = older_than.map(|days| now().saturating_sub(days * 86400));
+    let entries: Vec<Entry> = trash::all_entries()
+        .into_iter()
+        .filter(|e| targets.is_empty() || targets.contains(&e.target()))
+        .filter(|e| cutoff.is_none_or(|c| e.timestamp < c))
+        .collect();
+    if entries.is_empty() {
+        println!("Nothing to purge.");
+        return Ok(());
+    }
+
+    print_entries(&entries);
+    let prompt = format!("Permanently delete {} trashed worktree(s)?", entries.len());
+    if !yes && !confirm(&prompt)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for entry in &entries {
+        if let Err(e) = trash::remove(entry) {
+            eprintln!("Error: failed to purge {}: {:#}", entry.refname, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("Failed to purge {} of {} entries", failed, entries.len());
+    }
+    println!("Purged {} trashed worktree(s)", entries.len());
+    Ok(())
+}
@@ -1,49 +1,110 @@
 use std::ffi::OsStr;
-use std::path::Path;
 use std::process::Command;
 
 use clap_complete::engine::CompletionCandidate;
 
-/// Complete `yati activate` with `project/branch` targets from `~/.yati/`.
-pub fn complete_activate_target(current: &OsStr) -> Vec<CompletionCandidate> {
+use crate::metadata::Metadata;
+use crate::{trash, worktrees};
+
+/// Complete `project/branch` targets of all yati worktrees, with their descriptions.
+pub fn complete_worktree_target(current: &OsStr) -> Vec<CompletionCandidate> {
     let prefix = current.to_string_lossy();
-    let Some(yati_base) = dirs::home_dir().map(|h| h.join(".yati")) else {
-        return Vec::new();
-    };
-    if !yati_base.exists() {
-        return Vec::new();
-    }
+    let metadata = Metadata::load_all();
+    worktrees::discover()
+        .unwrap_or_default()
+        .iter()
+        .filter(|w| w.session_name().starts_with(prefix.as_ref()))
+        .map(|w| {
+            let description = metadata.get(w).and_then(|m| m.description.clone());
+            CompletionCandidate::new(w.session_name()).help(description.map(Into::into))
+        })
+        .collect()
+}
+
+/// Complete `project/branch` targets of trashed worktrees.
+pub fn complete_trash_target(current: &OsStr) -> Vec<CompletionCandidate> {
+    let prefix = current.to_string_lossy();
+    let mut targets: Vec<String> = trash::all_entries()
+        .iter()
+        .map(|e| e.target())
+        .filter(|target| target.starts_with(prefix.as_ref()))
+        .collect();
+    targets.dedup();
+    targets.into_iter().map(CompletionCandidate::new).collect()
+}
 
-    let mut candidates = Vec::new();
-    let projects = match std::fs::read_dir(&yati_base) {
-        Ok(entries) => entries,
+/// Complete project names of all yati worktrees.
+pub fn complete_project(current: &OsStr) -> Vec<CompletionCandidate> {
+    let prefix = current.to_string_lossy();
+    let mut projects: Vec<String> = worktrees::discover()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|w| w.project)
+        .filter(|p| p.starts_with(prefix.as_ref()))
+        .collect();
+    projects.dedup();
+    projects.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Complete `yati create` with existing local branch names, followed by branches
+/// that only exist on a remote (offered without the remote prefix).
+pub fn complete_create_branch(current: &OsStr) -> Vec<CompletionCandidate> {
+    let prefix = current.to_string_lossy();
+    let output = match Command::new("git")
+        .args([
+            "for-each-ref",
+            "refs/heads/",
+            "refs/remotes/",
+            "--format=%(refname)",
+        ])
+        .output()
+    {
+        Ok(o) => o,
         Err(_) => return Vec::new(),
     };
 
-    for entry in projects.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
-        }
-        let project_name = entry.file_name();
-        let project_str = project_name.to_string_lossy();
+    if !output.status.success() {
+        return Vec::new();
+    }
 
-        for branch in find_worktrees(&path) {
-            let target = format!("{}/{}", project_str, branch);
-            if target.starts_with(prefix.as_ref()) {
-                candidates.push(CompletionCandidate::new(target));
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut local = Vec::new();
+    let mut remote = Vec::new();
+    for refname in text.lines() {
+        if let Some(branch) = refname.strip_prefix("refs/heads/") {
+            local.push(branch.to_string());
+        } else if let Some(rest) = refname.strip_prefix("refs/remotes/") {
+            // Strip the remote name: refs/remotes/<remote>/<branch>
+            if let Some((_, branch)) = rest.split_once('/')
+                && branch != "HEAD"
+            {
+                remote.push(branch.to_string());
             }
         }
     }
+    remote.retain(|b| !local.contains(b));
+    remote.sort();
+    remote.dedup();
 
-    candidates
+    local
+        .into_iter()
+        .chain(remote)
+        .filter(|branch| branch.starts_with(prefix.as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
 }
 
-/// Complete `yati create` with existing git branch names.
-pub fn complete_create_branch(current: &OsStr) -> Vec<CompletionCandidate> {
+/// Complete `yati create --from` with local branches, remote-tracking branches and tags.
+pub fn complete_base_ref(current: &OsStr) -> Vec<CompletionCandidate> {
     let prefix = current.to_string_lossy();
     let output = match Command::new("git")
-        .args(["for-each-ref", "refs/heads/", "--format=%(refname:short)"])
+        .args([
+            "for-each-ref",
+            "refs/heads/",
+            "refs/remotes/",
+            "refs/tags/",
+            "--format=%(refname:short)",
+        ])
         .output()
     {
         Ok(o) => o,
@@ -60,34 +121,3 @@ pub fn complete_create_branch(current: &OsStr) -> Vec<CompletionCandidate> {
         .map(|line| CompletionCandidate::new(line.to_string()))
         .collect()
 }
-
-/// Find worktree directories under `base`.
-/// A worktree is identified by containing a `.git` file (not directory).
-fn find_worktrees(base: &Path) -> Vec<String> {
-    let mut results = Vec::new();
-    find_worktrees_recursive(base, base, &mut results);
-    results
-}
-
-fn find_worktrees_recursive(base: &Path, current: &Path, results: &mut Vec<String>) {
-    let entries = match std::fs::read_dir(current) {
-        Ok(entries) => entries,
-        Err(_) => return,
-    };
-
-    for entry in entries.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
-        }
-
-        let git_path = path.join(".git");
-        if git_path.exists() && git_path.is_file() {
-            if let Ok(rel) = path.strip_prefix(base) {
-                results.push(rel.to_string_lossy().to_string());
-            }
-        } else {
-            find_worktrees_recursive(base, &path, results);
-        }
-    }
-}
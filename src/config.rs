@@ -1,20 +1,104 @@
-use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::path::Path;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
 
-#[derive(Debug, Deserialize, Default, Clone)]
+use crate::projects::{self, Registry};
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct WindowConfig {
     pub name: String,
+    /// Command for a window with a single pane; windows with `panes` set it per pane.
     pub command: Option<String>,
+    /// Working directory relative to the worktree, also used by panes without their own.
+    pub cwd: Option<String>,
+    /// Environment variables set in every pane of the window.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// `even-horizontal`, `even-vertical`, `main-horizontal`, `main-vertical`, `tiled`,
+    /// or a custom tmux layout string.
+    pub layout: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panes: Vec<PaneConfig>,
 }
 
-#[derive(Debug, Deserialize, Default)]
+impl WindowConfig {
+    /// Whether panes of the window's layout are split side by side rather than stacked.
+    pub fn splits_horizontally(&self) -> bool {
+        matches!(
+            self.layout.as_deref(),
+            Some("even-horizontal" | "main-vertical")
+        )
+    }
+
+    /// The directory a pane with working directory `cwd` starts in: `cwd`, or else the
+    /// window's, relative to `working_dir`. Fails if it doesn't exist.
+    pub fn pane_dir(&self, working_dir: &Path, cwd: Option<&str>) -> Result<PathBuf> {
+        let dir = match cwd.or(self.cwd.as_deref()) {
+            Some(cwd) => working_dir.join(cwd),
+            None => return Ok(working_dir.to_path_buf()),
+        };
+        if !dir.is_dir() {
+            bail!(
+                "Working directory {} of window '{}' does not exist",
+                dir.display(),
+                self.name
+            );
+        }
+        Ok(dir)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct PaneConfig {
+    pub command: Option<String>,
+    /// Working directory relative to the worktree; defaults to the window's.
+    pub cwd: Option<String>,
+    /// Width or height, in cells or as a percentage, e.g. `"30%"`.
+    pub size: Option<String>,
+    /// Select this pane once the window is set up, instead of the first one.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub focus: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(default)]
 pub struct TmuxConfig {
     pub windows: Vec<WindowConfig>,
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// Terminal multiplexer worktree sessions run in.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MultiplexerKind {
+    #[default]
+    Tmux,
+    /// Windows become tabs.
+    Zellij,
+    /// No sessions: only the worktree and hooks are managed.
+    None,
+}
+
+/// When `teardown` deletes the worktree's branch.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeleteBranch {
+    Always,
+    /// Only if the branch is merged (or squash-merged) into its recorded base.
+    IfMerged,
+    Never,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct TeardownConfig {
+    /// Unset means `git branch -d` decides, i.e. the branch is deleted only if git
+    /// considers it merged.
+    pub delete_branch: Option<DeleteBranch>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(default)]
 pub struct Config {
     pub copy_files: Vec<String>,
@@ -22,17 +106,372 @@ pub struct Config {
     pub post_create: Vec<String>,
     pub post_activate: Vec<String>,
     pub pre_teardown: Vec<String>,
+    pub project_name: Option<String>,
+    pub default_base: Option<String>,
+    pub worktree_root: Option<String>,
+    pub path_template: Option<String>,
+    /// Number of ports to reserve for each worktree.
+    pub ports: Option<u16>,
+    /// Inclusive range ports are reserved from.
+    pub port_range: Option<[u16; 2]>,
+    /// Defaults to tmux.
+    pub multiplexer: Option<MultiplexerKind>,
+    /// Environment variables set in the session and for hooks.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
     pub tmux: TmuxConfig,
+    pub teardown: TeardownConfig,
+    /// Lists and maps (e.g. `post_create`, `env`, `tmux.windows`) that replace the
+    /// inherited values from lower layers instead of extending them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replace: Vec<String>,
 }
 
+/// How a key is merged across layers. Keys not listed here are scalars, where the
+/// highest layer that sets them wins.
+const MERGE_KINDS: &[(&str, MergeKind)] = &[
+    ("copy_files", MergeKind::List),
+    ("exclude", MergeKind::List),
+    ("post_create", MergeKind::List),
+    ("post_activate", MergeKind::List),
+    ("pre_teardown", MergeKind::List),
+    ("env", MergeKind::Map),
+    ("tmux.windows", MergeKind::Named),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MergeKind {
+    /// Appended to.
+    List,
+    /// A list of tables merged by `name`: an upper item replaces the lower item with the
+    /// same name in place, and new names are added at the end.
+    Named,
+    /// A table merged by entry, the upper layer winning.
+    Map,
+}
+
+fn merge_kind(key: &str) -> Option<MergeKind> {
+    MERGE_KINDS
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, kind)| *kind)
+}
+
+/// A value merged across layers, keyed by its dotted path (e.g. `tmux.windows`) in
+/// `Merged`. Every value carries a `T`: the value itself when loading the config, or the
+/// value and the layer it came from for `yati config show --origin`.
+pub enum MergedValue<T> {
+    Scalar(T),
+    List(Vec<T>),
+    /// Items with their names.
+    Named(Vec<(String, T)>),
+    Map(BTreeMap<String, T>),
+}
+
+pub type Merged<T> = BTreeMap<String, MergedValue<T>>;
+
+/// Layer `layer` over `merged`, wrapping each of its values with `tag`.
+///
+/// - Scalars (including `ports`, `port_range`, `multiplexer` and
+///   `teardown.delete_branch`) set in `layer` win.
+/// - Lists are appended to, `env` is merged by variable and tmux windows by name, unless
+///   `layer` names them in `replace`, which discards the lower values.
+pub fn merge_layer<T>(
+    merged: &mut Merged<T>,
+    layer: &Config,
+    tag: impl Fn(toml::Value) -> T,
+) -> Result<()> {
+    let toml::Value::Table(mut table) =
+        toml::Value::try_from(layer).context("Failed to serialize config")?
+    else {
+        bail!("Config didn't serialize to a table");
+    };
+    table.remove("replace");
+    let replaces = |key: &str| layer.replace.iter().any(|r| r == key);
+
+    let mut values = Vec::new();
+    flatten(table, "", &mut values);
+    for (key, value) in values {
+        let Some(kind) = merge_kind(&key) else {
+            merged.insert(key, MergedValue::Scalar(tag(value)));
+            continue;
+        };
+        let replace = replaces(&key);
+        let lower = merged.entry(key).or_insert_with(|| match kind {
+            MergeKind::List => MergedValue::List(Vec::new()),
+            MergeKind::Named => MergedValue::Named(Vec::new()),
+            MergeKind::Map => MergedValue::Map(BTreeMap::new()),
+        });
+        match (lower, value) {
+            (MergedValue::List(lower), toml::Value::Array(items)) => {
+                if replace {
+                    lower.clear();
+                }
+                lower.extend(items.into_iter().map(&tag));
+            }
+            (MergedValue::Named(lower), toml::Value::Array(items)) => {
+                if replace {
+                    lower.clear();
+                }
+                for item in items {
+                    let name = item
+                        .get("name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or_default()
+                        .to_string();
+                    match lower.iter_mut().find(|(n, _)| *n == name) {
+                        Some(existing) => existing.1 = tag(item),
+                        None => lower.push((name, tag(item))),
+                    }
+                }
+            }
+            (MergedValue::Map(lower), toml::Value::Table(entries)) => {
+                if replace {
+                    lower.clear();
+                }
+                lower.extend(entries.into_iter().map(|(k, v)| (k, tag(v))));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Split `table` into values keyed by dotted path, leaving maps such as `env` whole.
+fn flatten(table: toml::Table, prefix: &str, values: &mut Vec<(String, toml::Value)>) {
+    for (key, value) in table {
+        let key = format!("{}{}", prefix, key);
+        match value {
+            toml::Value::Table(inner) if merge_kind(&key).is_none() => {
+                flatten(inner, &format!("{}.", key), values)
+            }
+            value => values.push((key, value)),
+        }
+    }
+}
+
+impl Config {
+    /// Build the config that `merged` values describe.
+    fn from_merged(merged: Merged<toml::Value>) -> Result<Config> {
+        let mut table = toml::Table::new();
+        for (key, value) in merged {
+            let value = match value {
+                MergedValue::Scalar(value) => value,
+                MergedValue::List(items) => toml::Value::Array(items),
+                MergedValue::Named(items) => {
+                    toml::Value::Array(items.into_iter().map(|(_, item)| item).collect())
+                }
+                MergedValue::Map(entries) => toml::Value::Table(entries.into_iter().collect()),
+            };
+            match key.split_once('.') {
+                Some((section, field)) => {
+                    if let toml::Value::Table(section) = table
+                        .entry(section)
+                        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    {
+                        section.insert(field.to_string(), value);
+                    }
+                }
+                None => {
+                    table.insert(key, value);
+                }
+            }
+        }
+        Config::deserialize(toml::Value::Table(table)).context("Failed to merge config layers")
+    }
+}
+
+/// Read one config layer from `path`, or `None` if the file doesn't exist.
+/// Read a repository config layer. A relative `worktree_root` is taken relative to
+/// `repo_root`.
+fn read_layer(path: &Path, repo_root: &Path) -> Result<Option<Config>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut config: Config = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    validate(&config, path)?;
+    anchor_worktree_root(&mut config, repo_root);
+    Ok(Some(config))
+}
+
+/// Make a relative `worktree_root` absolute by joining it to `dir`, so it doesn't
+/// depend on where yati runs. `~` paths are left to `paths::expand_tilde`.
+fn anchor_worktree_root(config: &mut Config, dir: &Path) {
+    if let Some(root) = &config.worktree_root
+        && !root.starts_with('~')
+        && Path::new(root).is_relative()
+    {
+        let mut anchored = dir.to_path_buf();
+        for component in Path::new(root).components() {
+            match component {
+                Component::ParentDir => {
+                    anchored.pop();
+                }
+                Component::CurDir => {}
+                component => anchored.push(component),
+            }
+        }
+        config.worktree_root = Some(anchored.to_string_lossy().to_string());
+    }
+}
+
+fn validate(config: &Config, path: &Path) -> Result<()> {
+    for field in &config.replace {
+        if merge_kind(field).is_none() {
+            bail!(
+                "Unknown field '{}' in `replace` in {} (expected one of: {})",
+                field,
+                path.display(),
+                MERGE_KINDS
+                    .iter()
+                    .map(|(key, _)| *key)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+    for window in &config.tmux.windows {
+        if window.command.is_some() && !window.panes.is_empty() {
+            bail!(
+                "Window '{}' in {} sets both `command` and `panes`; set the command of its first pane instead",
+                window.name,
+                path.display()
+            );
+        }
+        if window.panes.iter().filter(|p| p.focus).count() > 1 {
+            bail!(
+                "Window '{}' in {} focuses more than one pane",
+                window.name,
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// One source of configuration, lowest precedence first in `load_layers`.
+pub struct Layer {
+    /// The file (and section) the values came from.
+    pub source: String,
+    pub config: Config,
+}
+
+/// The untracked personal override for a repo: `yati.local.toml`, or failing that
+/// `.yati/config.local.toml`.
+fn local_config_path(repo_root: &Path) -> Option<PathBuf> {
+    [
+        repo_root.join("yati.local.toml"),
+        repo_root.join(".yati").join("config.local.toml"),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+}
+
+/// All config layers for the repository whose main worktree is `repo_root`, lowest first:
+/// the global config, its `[projects.<name>]` section for this project, the repo's
+/// shared `yati.toml`, and the local override.
+pub fn load_layers(repo_root: &Path) -> Result<Vec<Layer>> {
+    let shared_path = repo_root.join("yati.toml");
+    let shared = read_layer(&shared_path, repo_root)?;
+    let local_path = local_config_path(repo_root);
+    let local = match &local_path {
+        Some(path) => read_layer(path, repo_root)?,
+        None => None,
+    };
+
+    // Only the repo can name itself, since the name picks the global project section
+    let repo_named = Config {
+        project_name: local
+            .as_ref()
+            .and_then(|c| c.project_name.clone())
+            .or_else(|| shared.as_ref().and_then(|c| c.project_name.clone())),
+        ..Config::default()
+    };
+    let project = projects::name_for(repo_root, &repo_named, &Registry::load()?)?;
+
+    let mut layers = Vec::new();
+    let mut global = load_global_config()?;
+    let global_source = global_config_path()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    global.defaults.project_name = None;
+    layers.push(Layer {
+        source: global_source.clone(),
+        config: global.defaults,
+    });
+    if let Some(mut section) = global.projects.remove(&project) {
+        section.project_name = None;
+        layers.push(Layer {
+            source: format!("{} [projects.{}]", global_source, project),
+            config: section,
+        });
+    }
+    if let Some(config) = shared {
+        layers.push(Layer {
+            source: shared_path.display().to_string(),
+            config,
+        });
+    }
+    if let (Some(path), Some(config)) = (local_path, local) {
+        layers.push(Layer {
+            source: path.display().to_string(),
+            config,
+        });
+    }
+    Ok(layers)
+}
+
+/// Load the effective config for the repository whose main worktree is `repo_root`.
 pub fn load_config(repo_root: &Path) -> Result<Config> {
-    let config_path = repo_root.join("yati.toml");
+    let mut merged = Merged::new();
+    for layer in load_layers(repo_root)? {
+        merge_layer(&mut merged, &layer.config, |value| value)?;
+    }
+    Config::from_merged(merged)
+}
+
+/// The user's global config: defaults for every project at the top level, plus
+/// per-project sections keyed by project name.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct GlobalConfig {
+    #[serde(flatten)]
+    pub defaults: Config,
+    pub projects: BTreeMap<String, Config>,
+}
+
+/// `$XDG_CONFIG_HOME/yati/config.toml`, falling back to `~/.config/yati/config.toml`.
+pub fn global_config_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs::home_dir()?.join(".config"),
+    };
+    Some(base.join("yati").join("config.toml"))
+}
+
+pub fn load_global_config() -> Result<GlobalConfig> {
+    let Some(config_path) = global_config_path() else {
+        return Ok(GlobalConfig::default());
+    };
     if !config_path.exists() {
-        return Ok(Config::default());
+        return Ok(GlobalConfig::default());
     }
     let contents = std::fs::read_to_string(&config_path)
         .with_context(|| format!("Failed to read {}", config_path.display()))?;
-    let config: Config =
-        toml::from_str(&contents).with_context(|| "Failed to parse yati.toml")?;
+    let mut config: GlobalConfig = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", config_path.display()))?;
+    validate(&config.defaults, &config_path)?;
+    for section in config.projects.values() {
+        validate(section, &config_path)?;
+    }
+    // Relative roots in the global config are relative to the file
+    let dir = config_path.parent().unwrap_or(Path::new("/"));
+    anchor_worktree_root(&mut config.defaults, dir);
+    for section in config.projects.values_mut() {
+        anchor_worktree_root(section, dir);
+    }
     Ok(config)
 }
@@ -1,6 +1,7 @@
 use anyhow::{bail, Context, Result};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 
 pub fn repo_root() -> Result<PathBuf> {
     let output = Command::new("git")
@@ -20,16 +21,6 @@ pub fn repo_root() -> Result<PathBuf> {
     Ok(PathBuf::from(path))
 }
 
-pub fn repo_name() -> Result<String> {
-    let root = repo_root()?;
-    let name = root
-        .file_name()
-        .context("Could not determine repo name")?
-        .to_string_lossy()
-        .to_string();
-    Ok(name)
-}
-
 pub fn main_worktree_root() -> Result<PathBuf> {
     let entries = worktree_list()?;
     let main = entries
@@ -39,14 +30,36 @@ pub fn main_worktree_root() -> Result<PathBuf> {
     Ok(main.path)
 }
 
-pub fn main_repo_name() -> Result<String> {
-    let root = main_worktree_root()?;
-    let name = root
-        .file_name()
-        .context("Could not determine repo name")?
-        .to_string_lossy()
-        .to_string();
-    Ok(name)
+/// The main worktree of the repository that the worktree at `dir` belongs to.
+pub fn main_worktree_of(dir: &Path) -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args([
+            "-C",
+            &dir.to_string_lossy(),
+            "rev-parse",
+            "--path-format=absolute",
+            "--git-common-dir",
+        ])
+        .output()
+        .context("Failed to run git rev-parse")?;
+    if !output.status.success() {
+        bail!(
+            "Not a git worktree: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let common_dir = PathBuf::from(
+        String::from_utf8(output.stdout)
+            .context("Invalid UTF-8 in git output")?
+            .trim(),
+    );
+    // Non-bare repositories keep their common dir in <main worktree>/.git
+    if common_dir.file_name().is_some_and(|n| n == ".git")
+        && let Some(parent) = common_dir.parent()
+    {
+        return Ok(parent.to_path_buf());
+    }
+    Ok(common_dir)
 }
 
 pub fn validate_branch_name(name: &str) -> Result<()> {
@@ -60,24 +73,152 @@ pub fn validate_branch_name(name: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn worktree_add(path: &Path, branch: &str) -> Result<()> {
-    let output = Command::new("git")
+pub fn branch_exists(branch: &str) -> bool {
+    Command::new("git")
         .args([
-            "worktree",
-            "add",
-            "-b",
-            branch,
-            &path.to_string_lossy(),
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{}", branch),
         ])
         .output()
-        .context("Failed to run git worktree add")?;
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Check that `rev` resolves to a commit (branch, tag, SHA, `origin/main`, ...).
+pub fn validate_ref(rev: &str) -> Result<()> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)])
+        .output()
+        .context("Failed to run git rev-parse")?;
+    if !output.status.success() {
+        bail!("Unknown base ref: {}", rev);
+    }
+    Ok(())
+}
+
+/// Name of the currently checked out branch, or the commit SHA if HEAD is detached.
+pub fn current_ref() -> Result<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()
+        .context("Failed to run git symbolic-ref")?;
     if output.status.success() {
-        return Ok(());
+        return Ok(String::from_utf8(output.stdout)
+            .context("Invalid UTF-8 in git output")?
+            .trim()
+            .to_string());
     }
 
-    // Branch might already exist, try without -b
     let output = Command::new("git")
-        .args(["worktree", "add", &path.to_string_lossy(), branch])
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .context("Failed to run git rev-parse")?;
+    if !output.status.success() {
+        bail!(
+            "git rev-parse HEAD failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)
+        .context("Invalid UTF-8 in git output")?
+        .trim()
+        .to_string())
+}
+
+/// Names of all configured remotes.
+pub fn remotes() -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(["remote"])
+        .output()
+        .context("Failed to run git remote")?;
+    if !output.status.success() {
+        bail!(
+            "git remote failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)
+        .context("Invalid UTF-8 in git output")?
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect())
+}
+
+/// URL of the `origin` remote of the repository at `repo_dir`, if it has one.
+pub fn remote_url(repo_dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .current_dir(repo_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let url = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if url.is_empty() { None } else { Some(url) }
+}
+
+/// Find the remote-tracking branch for `branch` (e.g. `origin/feature/x`) on any remote.
+///
+/// If several remotes carry the branch, `origin` wins; otherwise the choice is ambiguous
+/// and the caller has to pick one with `--from`.
+pub fn find_remote_branch(branch: &str) -> Result<Option<String>> {
+    let candidates: Vec<String> = remotes()?
+        .into_iter()
+        .filter(|remote| {
+            Command::new("git")
+                .args([
+                    "show-ref",
+                    "--verify",
+                    "--quiet",
+                    &format!("refs/remotes/{}/{}", remote, branch),
+                ])
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Ok(None),
+        [remote] => Ok(Some(format!("{}/{}", remote, branch))),
+        _ if candidates.iter().any(|r| r == "origin") => Ok(Some(format!("origin/{}", branch))),
+        _ => bail!(
+            "Branch '{}' exists on multiple remotes ({}). Use --from <remote>/{} to pick one.",
+            branch,
+            candidates.join(", "),
+            branch
+        ),
+    }
+}
+
+/// Where the branch checked out in a new worktree comes from.
+pub enum BranchStart {
+    /// Check out an existing local branch.
+    Existing,
+    /// Create a new branch from a base ref.
+    From(String),
+    /// Create a new local branch tracking a remote-tracking branch such as `origin/feature`.
+    Track(String),
+}
+
+/// Add a worktree at `path` for `branch`, creating the branch as described by `start`.
+pub fn worktree_add(path: &Path, branch: &str, start: &BranchStart, repo_dir: &Path) -> Result<()> {
+    let path_str = path.to_string_lossy();
+    let args: Vec<&str> = match start {
+        BranchStart::Existing => vec!["worktree", "add", &path_str, branch],
+        BranchStart::From(base) => vec!["worktree", "add", "-b", branch, &path_str, base],
+        BranchStart::Track(upstream) => {
+            vec!["worktree", "add", "--track", "-b", branch, &path_str, upstream]
+        }
+    };
+
+    let output = Command::new("git")
+        .args(&args)
+        .current_dir(repo_dir)
         .output()
         .context("Failed to run git worktree add")?;
     if !output.status.success() {
@@ -89,6 +230,36 @@ pub fn worktree_add(path: &Path, branch: &str) -> Result<()> {
     Ok(())
 }
 
+/// Record the ref a yati branch was created from in `branch.<name>.yatiBase`.
+pub fn set_branch_base(branch: &str, base: &str, repo_dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["config", &format!("branch.{}.yatiBase", branch), base])
+        .current_dir(repo_dir)
+        .output()
+        .context("Failed to run git config")?;
+    if !output.status.success() {
+        bail!(
+            "git config failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// The base ref recorded for `branch` when yati created it, if any.
+pub fn branch_base(branch: &str, repo_dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["config", "--get", &format!("branch.{}.yatiBase", branch)])
+        .current_dir(repo_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let base = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if base.is_empty() { None } else { Some(base) }
+}
+
 fn has_dirty_tracked_files(path: &Path, repo_dir: &Path) -> Result<bool> {
     let output = Command::new("git")
         .args(["-C", &path.to_string_lossy(), "status", "--porcelain"])
@@ -117,6 +288,26 @@ fn has_untracked_non_ignored_files(path: &Path, repo_dir: &Path) -> Result<bool>
     Ok(!text.trim().is_empty())
 }
 
+/// The ignored files and directories `clean_ignored_files` would delete, relative to
+/// the worktree at `path`.
+pub fn ignored_files(path: &Path) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(["-C", &path.to_string_lossy(), "clean", "-ndX"])
+        .output()
+        .context("Failed to run git clean")?;
+    if !output.status.success() {
+        bail!(
+            "git clean -ndX failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("Would remove "))
+        .map(str::to_string)
+        .collect())
+}
+
 fn clean_ignored_files(path: &Path, repo_dir: &Path) -> Result<()> {
     let output = Command::new("git")
         .args(["-C", &path.to_string_lossy(), "clean", "-fdX"])
@@ -167,7 +358,6 @@ pub fn worktree_remove(path: &Path, force: bool, repo_dir: &Path) -> Result<()>
 #[derive(Debug)]
 pub struct WorktreeEntry {
     pub path: PathBuf,
-    pub head: String,
     pub branch: String,
 }
 
@@ -186,6 +376,56 @@ pub fn worktree_prune(repo_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Repair the links between the worktree at `path` and the repository at `repo_dir`,
+/// e.g. after either was moved by hand.
+pub fn worktree_repair(path: &Path, repo_dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["worktree", "repair"])
+        .arg(path)
+        .current_dir(repo_dir)
+        .output()
+        .context("Failed to run git worktree repair")?;
+    if !output.status.success() {
+        bail!(
+            "git worktree repair failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+pub fn worktree_move(from: &Path, to: &Path, repo_dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["worktree", "move"])
+        .arg(from)
+        .arg(to)
+        .current_dir(repo_dir)
+        .output()
+        .context("Failed to run git worktree move")?;
+    if !output.status.success() {
+        bail!(
+            "git worktree move failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+pub fn branch_rename(old: &str, new: &str, repo_dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["branch", "-m", old, new])
+        .current_dir(repo_dir)
+        .output()
+        .context("Failed to run git branch -m")?;
+    if !output.status.success() {
+        bail!(
+            "git branch -m failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
 pub fn branch_delete(branch: &str, force: bool, repo_dir: &Path) -> Result<()> {
     let flag = if force { "-D" } else { "-d" };
     let output = Command::new("git")
@@ -249,40 +489,434 @@ pub fn head_short(worktree_dir: &Path) -> Result<String> {
         .to_string())
 }
 
+/// Whether the worktree has any uncommitted changes or untracked files.
+pub fn is_dirty(worktree_dir: &Path) -> Result<bool> {
+    let output = Command::new("git")
+        .args(["-C", &worktree_dir.to_string_lossy(), "status", "--porcelain"])
+        .output()
+        .context("Failed to run git status")?;
+    if !output.status.success() {
+        bail!(
+            "git status failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(!output.stdout.is_empty())
+}
+
+/// Commits in `left` but not `right`, and in `right` but not `left`, or `None` if either
+/// side doesn't resolve (e.g. no upstream is configured).
+pub fn ahead_behind(worktree_dir: &Path, left: &str, right: &str) -> Option<(u32, u32)> {
+    let output = Command::new("git")
+        .args([
+            "-C",
+            &worktree_dir.to_string_lossy(),
+            "rev-list",
+            "--left-right",
+            "--count",
+            &format!("{}...{}", left, right),
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    let mut counts = text.split_whitespace().map(|n| n.parse::<u32>());
+    match (counts.next(), counts.next()) {
+        (Some(Ok(ahead)), Some(Ok(behind))) => Some((ahead, behind)),
+        _ => None,
+    }
+}
+
+/// Unix timestamp of the last commit on HEAD.
+pub fn last_commit_time(worktree_dir: &Path) -> Option<u64> {
+    let output = Command::new("git")
+        .args(["-C", &worktree_dir.to_string_lossy(), "log", "-1", "--format=%ct"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+}
+
+/// Trimmed stdout of a git command run in `dir`, or `None` if it fails.
+fn query(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// The branch checked out in the worktree at `dir`, or `None` if HEAD is detached.
+pub fn checked_out_branch(dir: &Path) -> Option<String> {
+    query(dir, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+}
+
+/// The commit `rev` points to, if it exists.
+pub fn resolve_commit(dir: &Path, rev: &str) -> Option<String> {
+    query(dir, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)])
+}
+
+/// The repository's default branch: what `origin/HEAD` points to, or else a local
+/// `main` or `master`.
+pub fn default_branch(dir: &Path) -> Option<String> {
+    if let Some(head) = query(dir, &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"]) {
+        return Some(head);
+    }
+    ["main", "master"]
+        .into_iter()
+        .find(|b| resolve_commit(dir, &format!("refs/heads/{}", b)).is_some())
+        .map(str::to_string)
+}
+
+/// Whether `branch` has moved since it was created from `HEAD` or one of `bases`,
+/// judging by its reflog. Branches created from anything else (say, a remote branch
+/// with someone's work on it) and branches whose reflog has expired count as having
+/// moved.
+pub fn branch_has_moved(dir: &Path, branch: &str, bases: &[String]) -> bool {
+    let Some(log) = query(
+        dir,
+        &["reflog", "show", "--format=%gs", &format!("refs/heads/{}", branch)],
+    ) else {
+        return true;
+    };
+    let mut entries = log.lines();
+    match (entries.next(), entries.next()) {
+        (Some(entry), None) => entry
+            .strip_prefix("branch: Created from ")
+            .is_none_or(|from| from != "HEAD" && !bases.iter().any(|b| b == from)),
+        _ => true,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeState {
+    /// The branch tip is reachable from the base.
+    Merged,
+    /// The branch's changes landed on the base as different commits (squash or rebase).
+    Squashed,
+}
+
+/// Whether the changes on `branch` have landed on `base`.
+///
+/// Rebase merges are detected by `git cherry` finding every commit of the branch on
+/// `base`, squash merges by the branch's whole diff against the merge base having the
+/// same patch-id as a commit on `base`. Nothing is written to the repository.
+pub fn merge_state(dir: &Path, branch: &str, base: &str) -> Option<MergeState> {
+    let is_ancestor = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["merge-base", "--is-ancestor", branch, base])
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if is_ancestor {
+        return Some(MergeState::Merged);
+    }
+
+    let tree = query(dir, &["rev-parse", &format!("{}^{{tree}}", branch)])?;
+    if query(dir, &["rev-parse", &format!("{}^{{tree}}", base)]).as_ref() == Some(&tree) {
+        return Some(MergeState::Squashed);
+    }
+    let cherry = query(dir, &["cherry", base, branch])?;
+    if !cherry.is_empty() && cherry.lines().all(|line| line.starts_with('-')) {
+        return Some(MergeState::Squashed);
+    }
+
+    let merge_base = query(dir, &["merge-base", base, branch])?;
+    let squashed = patch_ids(dir, &["diff", &merge_base, branch])?;
+    let range = format!("{}..{}", merge_base, base);
+    let landed = patch_ids(dir, &["log", "-p", "--no-merges", &range])?;
+    squashed
+        .first()
+        .is_some_and(|id| landed.contains(id))
+        .then_some(MergeState::Squashed)
+}
+
+/// The stable patch-ids of the patches `git <args>` prints.
+fn patch_ids(dir: &Path, args: &[&str]) -> Option<Vec<String>> {
+    let patches = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+    if !patches.status.success() {
+        return None;
+    }
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["patch-id", "--stable"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(&patches.stdout).ok()?;
+    let output = child.wait_with_output().ok()?;
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Whether `branch` tracks a remote branch that no longer exists (as of the last fetch).
+pub fn upstream_gone(dir: &Path, branch: &str) -> bool {
+    query(
+        dir,
+        &[
+            "for-each-ref",
+            "--format=%(upstream:track)",
+            &format!("refs/heads/{}", branch),
+        ],
+    )
+    .is_some_and(|track| track == "[gone]")
+}
+
+pub fn fetch_prune(repo_dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["fetch", "--all", "--prune", "--quiet"])
+        .current_dir(repo_dir)
+        .output()
+        .context("Failed to run git fetch")?;
+    if !output.status.success() {
+        bail!(
+            "git fetch failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Commits on `branch` that aren't reachable from any remote-tracking branch or from
+/// `base`, as `<short hash> <subject>` lines, newest first.
+pub fn unpushed_commits(dir: &Path, branch: &str, base: Option<&str>) -> Result<Vec<String>> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C")
+        .arg(dir)
+        .args(["log", "--format=%h %s", &format!("refs/heads/{}", branch), "--not", "--remotes"]);
+    if let Some(base) = base.and_then(|b| resolve_commit(dir, b)) {
+        cmd.arg(base);
+    }
+    let output = cmd.output().context("Failed to run git log")?;
+    if !output.status.success() {
+        bail!(
+            "git log failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Push `branch` to its configured remote (or `origin`, or the only remote) and set it
+/// as the upstream.
+pub fn push_branch(dir: &Path, branch: &str) -> Result<()> {
+    let remote = match query(dir, &["config", "--get", &format!("branch.{}.remote", branch)]) {
+        Some(remote) if remote != "." => remote,
+        _ => {
+            let remotes: Vec<String> = query(dir, &["remote"])
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect();
+            match remotes.as_slice() {
+                [] => bail!("No remote to push '{}' to", branch),
+                [only] => only.clone(),
+                _ if remotes.iter().any(|r| r == "origin") => "origin".to_string(),
+                _ => bail!(
+                    "Several remotes and no 'origin'; set an upstream for '{}' first",
+                    branch
+                ),
+            }
+        }
+    };
+    println!("Pushing '{}' to {}...", branch, remote);
+    let output = Command::new("git")
+        .args(["push", "--set-upstream", &remote, branch])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git push")?;
+    if !output.status.success() {
+        bail!(
+            "git push failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Point `refname` at the commit `rev` resolves to.
+pub fn update_ref(dir: &Path, refname: &str, rev: &str) -> Result<()> {
+    let output = Command::new("git")
+        .args(["update-ref", refname, rev])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git update-ref")?;
+    if !output.status.success() {
+        bail!(
+            "git update-ref failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Point the new ref `refname` at `rev`, failing if it already exists.
+pub fn create_ref(dir: &Path, refname: &str, rev: &str) -> Result<()> {
+    let output = Command::new("git")
+        .args(["update-ref", refname, rev, ""])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git update-ref")?;
+    if !output.status.success() {
+        bail!(
+            "git update-ref failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Commit the full state of the worktree at `dir`, including uncommitted changes and
+/// untracked (but not ignored) files, on top of HEAD without touching the index or any
+/// branch. Returns the new commit.
+pub fn commit_worktree_state(dir: &Path, message: &str) -> Result<String> {
+    let index = std::env::temp_dir().join(format!("yati-index-{}", std::process::id()));
+    let git = |args: &[&str]| -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .env("GIT_INDEX_FILE", &index)
+            .output()
+            .with_context(|| format!("Failed to run git {}", args[0]))?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let result = git(&["read-tree", "HEAD"])
+        .and_then(|_| git(&["add", "--all"]))
+        .and_then(|_| git(&["write-tree"]))
+        .and_then(|tree| git(&["commit-tree", &tree, "-p", "HEAD", "-m", message]));
+    let _ = std::fs::remove_file(&index);
+    result
+}
+
+/// Make the files in the worktree at `dir` match the tree of `source`, leaving the index
+/// alone so the differences show up as uncommitted changes.
+pub fn restore_worktree_files(dir: &Path, source: &str) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["restore", "--source", source, "--worktree", "--", "."])
+        .output()
+        .context("Failed to run git restore")?;
+    if !output.status.success() {
+        bail!(
+            "git restore failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+pub fn branch_create(branch: &str, start: &str, repo_dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["branch", branch, start])
+        .current_dir(repo_dir)
+        .output()
+        .context("Failed to run git branch")?;
+    if !output.status.success() {
+        bail!(
+            "git branch failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Names of the refs under `prefix` (e.g. `refs/yati/trash/`).
+pub fn list_refs(dir: &Path, prefix: &str) -> Vec<String> {
+    query(dir, &["for-each-ref", "--format=%(refname)", prefix])
+        .map(|refs| refs.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+pub fn delete_ref(dir: &Path, refname: &str) -> Result<()> {
+    let output = Command::new("git")
+        .args(["update-ref", "-d", refname])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git update-ref")?;
+    if !output.status.success() {
+        bail!(
+            "git update-ref -d failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// The message body of the commit `rev`.
+pub fn commit_body(dir: &Path, rev: &str) -> Option<String> {
+    query(dir, &["show", "-s", "--format=%b", rev])
+}
+
+/// Whether `rev` and its first parent have the same tree.
+pub fn same_tree_as_parent(dir: &Path, rev: &str) -> bool {
+    let tree = |r: &str| query(dir, &["rev-parse", &format!("{}^{{tree}}", r)]);
+    tree(rev).is_some() && tree(rev) == tree(&format!("{}^", rev))
+}
+
+/// Whether `path` (relative to the worktree at `dir`) is ignored by git.
+pub fn is_ignored(dir: &Path, path: &str) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["check-ignore", "-q", path])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
 fn parse_worktree_porcelain(text: &str) -> Result<Vec<WorktreeEntry>> {
     let mut entries = Vec::new();
     let mut path = None;
-    let mut head = String::new();
     let mut branch = String::new();
 
     for line in text.lines() {
         if let Some(p) = line.strip_prefix("worktree ") {
             path = Some(PathBuf::from(p));
-        } else if let Some(h) = line.strip_prefix("HEAD ") {
-            head = h.to_string();
         } else if let Some(b) = line.strip_prefix("branch ") {
             // branch is like refs/heads/main
             branch = b
                 .strip_prefix("refs/heads/")
                 .unwrap_or(b)
                 .to_string();
-        } else if line.is_empty() {
-            if let Some(p) = path.take() {
-                entries.push(WorktreeEntry {
-                    path: p,
-                    head: std::mem::take(&mut head),
-                    branch: std::mem::take(&mut branch),
-                });
-            }
+        } else if line.is_empty()
+            && let Some(p) = path.take()
+        {
+            entries.push(WorktreeEntry {
+                path: p,
+                branch: std::mem::take(&mut branch),
+            });
         }
     }
     // Handle last entry if no trailing blank line
     if let Some(p) = path.take() {
-        entries.push(WorktreeEntry {
-            path: p,
-            head,
-            branch,
-        });
+        entries.push(WorktreeEntry { path: p, branch });
     }
     Ok(entries)
 }
@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use crate::vars::Vars;
+
+/// Run each hook with `sh -c` in `dir`, with the `YATI_*` variables exported.
+/// A failing hook only produces a warning.
+pub fn run(phase: &str, hooks: &[String], dir: &Path, vars: &Vars) -> Result<()> {
+    for hook in hooks {
+        println!("Running {} hook: {}", phase, hook);
+        let status = Command::new("sh")
+            .args(["-c", hook])
+            .current_dir(dir)
+            .envs(vars.env())
+            .status()
+            .with_context(|| format!("Failed to run hook: {}", hook))?;
+        if !status.success() {
+            eprintln!("Warning: {} hook failed: {}", phase, hook);
+        }
+    }
+    Ok(())
+}
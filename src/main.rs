@@ -4,20 +4,94 @@ mod completions;
 mod config;
 mod copy;
 mod git;
+mod hooks;
+mod metadata;
+mod multiplexer;
+mod paths;
+mod picker;
+mod ports;
+mod projects;
+mod time;
 mod tmux;
+mod trash;
+mod vars;
+mod worktrees;
+mod zellij;
 
 use clap::{CommandFactory, Parser};
 use clap_complete::CompleteEnv;
-use cli::{Cli, Command};
+use cli::{Cli, Command, ConfigAction, TrashAction};
 
 fn main() -> anyhow::Result<()> {
-    CompleteEnv::with_factory(|| Cli::command()).complete();
+    CompleteEnv::with_factory(Cli::command).complete();
     let cli = Cli::parse();
     match cli.command {
-        Command::Activate { target } => commands::activate::run(&target),
-        Command::Create { branch_name } => commands::create::run(&branch_name),
+        Command::Activate {
+            target,
+            pick,
+            no_session,
+        } => commands::activate::run(target.as_deref(), pick, no_session),
+        Command::Create {
+            branch_name,
+            from,
+            keep_on_failure,
+            dry_run,
+            no_session,
+        } => commands::create::run(
+            &branch_name,
+            from.as_deref(),
+            keep_on_failure,
+            dry_run,
+            no_session,
+        ),
         Command::Deactivate => commands::deactivate::run(),
-        Command::Teardown { force } => commands::teardown::run(force),
-        Command::List => commands::list::run(),
+        Command::Teardown {
+            targets,
+            force,
+            push,
+            archive,
+            trash,
+            with_ignored,
+            dry_run,
+            keep_branch,
+        } => commands::teardown::run(
+            &targets,
+            commands::teardown::Options {
+                force,
+                push,
+                archive,
+                trash,
+                with_ignored,
+                dry_run,
+                keep_branch,
+                ..Default::default()
+            },
+        ),
+        Command::Describe { target, text } => commands::describe::run(&target, text.as_deref()),
+        Command::Tag {
+            target,
+            tags,
+            remove,
+        } => commands::tag::run(&target, &tags, remove),
+        Command::Doctor { fix } => commands::doctor::run(fix),
+        Command::Rename { target, new_branch } => commands::rename::run(&target, &new_branch),
+        Command::Adopt {
+            path,
+            move_into_layout,
+        } => commands::adopt::run(&path, move_into_layout),
+        Command::Restore { target, at } => commands::restore::run(&target, at),
+        Command::Trash { action } => match action {
+            TrashAction::List => commands::trash::list(),
+            TrashAction::Purge {
+                targets,
+                older_than,
+                yes,
+            } => commands::trash::purge(&targets, older_than, yes),
+        },
+        Command::List(args) => commands::list::run(&args),
+        Command::Prune(args) => commands::prune::run(&args),
+        Command::Config { action } => match action {
+            ConfigAction::Show { origin } => commands::config::show(origin),
+        },
     }
 }
@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+use crate::worktrees::Worktree;
+
+/// What yati remembers about a worktree beyond what git and tmux know.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorktreeMeta {
+    /// Unix timestamp of the `yati create` that made the worktree.
+    pub created: Option<u64>,
+    /// The command line that created the worktree.
+    pub command: Option<String>,
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Unix timestamp of the last `yati create` or `yati activate`.
+    pub last_activated: Option<u64>,
+    /// Where an adopted worktree lives when it is outside the yati layout.
+    pub path: Option<PathBuf>,
+}
+
+/// Metadata of one project's worktrees, keyed by branch, stored in
+/// `<root>/metadata/<project>.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectMetadata {
+    pub worktrees: BTreeMap<String, WorktreeMeta>,
+}
+
+pub fn metadata_dir() -> Result<PathBuf> {
+    Ok(paths::yati_root()?.join("metadata"))
+}
+
+fn project_path(project: &str) -> Result<PathBuf> {
+    Ok(metadata_dir()?.join(format!("{}.toml", project)))
+}
+
+impl ProjectMetadata {
+    pub fn load(project: &str) -> Result<ProjectMetadata> {
+        let path = project_path(project)?;
+        if !path.exists() {
+            return Ok(ProjectMetadata::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write the metadata back, removing the file once no worktrees are left.
+    pub fn save(&self, project: &str) -> Result<()> {
+        let path = project_path(project)?;
+        if self.worktrees.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let contents = toml::to_string(self).context("Failed to serialize worktree metadata")?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// The metadata recorded for `worktree`, or defaults if there is none.
+pub fn get(worktree: &Worktree) -> Result<WorktreeMeta> {
+    Ok(ProjectMetadata::load(&worktree.project)?
+        .worktrees
+        .remove(&worktree.branch)
+        .unwrap_or_default())
+}
+
+/// Change the metadata of `worktree` with `f`, creating it if needed, and save it.
+pub fn update(worktree: &Worktree, f: impl FnOnce(&mut WorktreeMeta)) -> Result<()> {
+    let mut metadata = ProjectMetadata::load(&worktree.project)?;
+    f(metadata.worktrees.entry(worktree.branch.clone()).or_default());
+    metadata.save(&worktree.project)
+}
+
+/// Forget `worktree`.
+pub fn remove(worktree: &Worktree) -> Result<()> {
+    forget(&worktree.project, &worktree.branch)
+}
+
+/// Forget the worktree of `branch` in `project`, which may no longer exist.
+pub fn forget(project: &str, branch: &str) -> Result<()> {
+    let mut metadata = ProjectMetadata::load(project)?;
+    if metadata.worktrees.remove(branch).is_some() {
+        metadata.save(project)?;
+    }
+    Ok(())
+}
+
+/// Move the metadata of `worktree` over to `branch`, recording `path` for worktrees
+/// outside the yati layout.
+pub fn rename(worktree: &Worktree, branch: &str, path: Option<PathBuf>) -> Result<()> {
+    let mut metadata = ProjectMetadata::load(&worktree.project)?;
+    if let Some(mut meta) = metadata.worktrees.remove(&worktree.branch) {
+        meta.path = path;
+        metadata.worktrees.insert(branch.to_string(), meta);
+        metadata.save(&worktree.project)?;
+    }
+    Ok(())
+}
+
+/// The command line yati was run with, e.g. `yati create feature --from main`.
+pub fn command_line() -> String {
+    std::env::args()
+        .enumerate()
+        .map(|(i, arg)| match i {
+            0 => Path::new(&arg)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(arg),
+            _ => arg,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Metadata of every project, for commands that look at many worktrees at once.
+/// Unreadable files are skipped with a warning.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    projects: HashMap<String, ProjectMetadata>,
+}
+
+impl Metadata {
+    pub fn load_all() -> Metadata {
+        let Ok(entries) = metadata_dir().and_then(|dir| Ok(std::fs::read_dir(dir)?)) else {
+            return Metadata::default();
+        };
+        let projects = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().strip_suffix(".toml")?.to_string();
+                match ProjectMetadata::load(&name) {
+                    Ok(metadata) => Some((name, metadata)),
+                    Err(e) => {
+                        eprintln!("Warning: {:#}", e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Metadata { projects }
+    }
+
+    pub fn get(&self, worktree: &Worktree) -> Option<&WorktreeMeta> {
+        self.projects
+            .get(&worktree.project)?
+            .worktrees
+            .get(&worktree.branch)
+    }
+
+    /// Project and branch of every worktree with metadata.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.projects
+            .iter()
+            .flat_map(|(project, metadata)| {
+                metadata
+                    .worktrees
+                    .keys()
+                    .map(|branch| (project.clone(), branch.clone()))
+            })
+            .collect()
+    }
+
+    /// The branch of an adopted worktree of `project` living at `path`.
+    pub fn adopted(&self, project: &str, path: &Path) -> Option<String> {
+        self.projects
+            .get(project)?
+            .worktrees
+            .iter()
+            .find(|(_, meta)| meta.path.as_deref() == Some(path))
+            .map(|(branch, _)| branch.clone())
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::{Config, MultiplexerKind, WindowConfig};
+use crate::tmux::Tmux;
+use crate::zellij::Zellij;
+
+/// A terminal multiplexer that runs one session per worktree, named `project/branch`.
+pub trait Multiplexer {
+    /// Name shown in messages, e.g. `tmux`.
+    fn name(&self) -> &'static str;
+
+    /// Whether this process runs inside a session of this multiplexer.
+    fn is_inside(&self) -> bool;
+
+    /// Name of the session this process runs in, if any.
+    fn current_session(&self) -> Option<String>;
+
+    fn session_exists(&self, name: &str) -> bool;
+
+    /// Every running session with its last activity (Unix timestamp), if the
+    /// multiplexer tracks it. Empty if the multiplexer isn't running or installed.
+    fn sessions(&self) -> HashMap<String, Option<u64>>;
+
+    /// Create a detached session. `env` is set for every pane created in it.
+    fn new_session(&self, name: &str, working_dir: &Path, env: &[(String, String)]) -> Result<()>;
+
+    /// Update the environment of a running session, for panes created from now on.
+    fn set_environment(&self, session: &str, env: &[(String, String)]) -> Result<()>;
+
+    /// Set up the configured windows (tabs) in a session made by `new_session`.
+    fn setup_windows(&self, session: &str, working_dir: &Path, windows: &[WindowConfig])
+        -> Result<()>;
+
+    /// Attach to the session, or switch to it when already inside the multiplexer.
+    fn attach_or_switch(&self, name: &str) -> Result<()>;
+
+    /// Try switching to the session we came from. Returns true only if there was one.
+    fn switch_to_previous_session(&self) -> bool;
+
+    fn detach(&self) -> Result<()>;
+
+    /// Whether `kill_other_panes` is supported, i.e. a session can be torn down from
+    /// inside it.
+    fn can_kill_other_panes(&self) -> bool;
+
+    /// Kill all panes in the session except the one this process runs in.
+    fn kill_other_panes(&self, session: &str) -> Result<()>;
+
+    fn kill_session(&self, name: &str) -> Result<()>;
+
+    fn rename_session(&self, old: &str, new: &str) -> Result<()>;
+
+    /// The panes of `session` as `(pane id, description)`, for dry runs.
+    fn panes(&self, session: &str) -> Result<Vec<(String, String)>>;
+
+    /// The id of the pane this process runs in, if any.
+    fn current_pane(&self) -> Option<String>;
+}
+
+/// The backend for `kind`, or `None` for `multiplexer = "none"`.
+pub fn backend(kind: MultiplexerKind) -> Option<Box<dyn Multiplexer>> {
+    match kind {
+        MultiplexerKind::Tmux => Some(Box::new(Tmux)),
+        MultiplexerKind::Zellij => Some(Box::new(Zellij)),
+        MultiplexerKind::None => None,
+    }
+}
+
+/// The multiplexer selected by the `multiplexer` key, tmux by default.
+pub fn for_config(config: &Config) -> Option<Box<dyn Multiplexer>> {
+    backend(config.multiplexer.unwrap_or_default())
+}
+
+/// Every supported multiplexer, for commands that look at sessions of all projects.
+pub fn all() -> Vec<(MultiplexerKind, Box<dyn Multiplexer>)> {
+    [MultiplexerKind::Tmux, MultiplexerKind::Zellij]
+        .into_iter()
+        .filter_map(|kind| Some((kind, backend(kind)?)))
+        .collect()
+}
+
+/// The multiplexer this process runs inside of, if any.
+pub fn current() -> Option<Box<dyn Multiplexer>> {
+    all()
+        .into_iter()
+        .map(|(_, backend)| backend)
+        .find(|backend| backend.is_inside())
+}
+
+/// Name of the session this process runs in, whichever multiplexer it belongs to.
+pub fn current_session() -> Option<String> {
+    current()?.current_session()
+}
+
+/// Running sessions of every multiplexer with their last activity, if known.
+pub fn sessions() -> HashMap<String, Option<u64>> {
+    all()
+        .into_iter()
+        .flat_map(|(_, backend)| backend.sessions())
+        .collect()
+}
+
+/// Enter a worktree without a session: start `$SHELL` in `dir` when run from a terminal,
+/// otherwise print `dir` so scripts can `cd` into it.
+pub fn enter_without_session(dir: &Path, env: &[(String, String)]) -> Result<()> {
+    if !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()) {
+        println!("{}", dir.display());
+        return Ok(());
+    }
+    let shell = std::env::var("SHELL")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "sh".to_string());
+    println!("Starting {} in {} (exit to leave)", shell, dir.display());
+    // The shell's exit status is whatever its last command returned, so it is ignored
+    Command::new(&shell)
+        .current_dir(dir)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .status()
+        .with_context(|| format!("Failed to run {}", shell))?;
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+
+const DEFAULT_TEMPLATE: &str = "{root}/{project}/{branch}";
+
+/// Expand a leading `~` to the home directory.
+pub fn expand_tilde(path: &str) -> Result<PathBuf> {
+    if path == "~" {
+        return dirs::home_dir().context("Could not determine home directory");
+    }
+    if let Some(rest) = path.strip_prefix("~/") {
+        let home = dirs::home_dir().context("Could not determine home directory")?;
+        return Ok(home.join(rest));
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// Resolve symlinks in `path` if it exists, since git reports worktree paths resolved.
+fn resolved(path: PathBuf) -> PathBuf {
+    path.canonicalize().unwrap_or(path)
+}
+
+/// The global yati root: `$YATI_HOME`, then `worktree_root` from the global config,
+/// then `~/.yati`.
+pub fn yati_root() -> Result<PathBuf> {
+    if let Ok(home) = std::env::var("YATI_HOME")
+        && !home.is_empty()
+    {
+        return Ok(resolved(expand_tilde(&home)?));
+    }
+    if let Some(root) = config::load_global_config()?.defaults.worktree_root {
+        return Ok(resolved(expand_tilde(&root)?));
+    }
+    Ok(resolved(
+        dirs::home_dir()
+            .context("Could not determine home directory")?
+            .join(".yati"),
+    ))
+}
+
+/// The root a project's worktrees live under. `$YATI_HOME` wins over everything,
+/// then the project's `worktree_root` (from `yati.toml` or the global config layers),
+/// then the global root.
+pub fn project_root(config: &Config) -> Result<PathBuf> {
+    if std::env::var("YATI_HOME").is_ok_and(|h| !h.is_empty()) {
+        return yati_root();
+    }
+    match &config.worktree_root {
+        Some(root) => Ok(resolved(expand_tilde(root)?)),
+        None => yati_root(),
+    }
+}
+
+fn template(config: &Config) -> Result<String> {
+    let template = config
+        .path_template
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+    if template.matches("{branch}").count() != 1 {
+        bail!(
+            "path_template must contain {{branch}} exactly once: {}",
+            template
+        );
+    }
+    Ok(template)
+}
+
+/// Split the rendered template around `{branch}` into a path prefix and suffix.
+fn render_around_branch(config: &Config, project: &str) -> Result<(String, String)> {
+    let root = project_root(config)?;
+    let rendered = template(config)?
+        .replace("{root}", &root.to_string_lossy())
+        .replace("{project}", project);
+    let (prefix, suffix) = rendered
+        .split_once("{branch}")
+        .context("path_template must contain {branch}")?;
+
+    // Templates without {root} are relative to it
+    let prefix = if Path::new(prefix).is_absolute() || prefix.starts_with('~') {
+        expand_tilde(prefix)?.to_string_lossy().to_string()
+    } else {
+        let mut joined = root.to_string_lossy().to_string();
+        if !joined.ends_with('/') {
+            joined.push('/');
+        }
+        joined.push_str(prefix);
+        joined
+    };
+    Ok((prefix, suffix.to_string()))
+}
+
+/// Where the worktree for `branch` of `project` lives, rendered from `path_template`.
+pub fn worktree_path(config: &Config, project: &str, branch: &str) -> Result<PathBuf> {
+    let (prefix, suffix) = render_around_branch(config, project)?;
+    Ok(PathBuf::from(format!("{}{}{}", prefix, branch, suffix)))
+}
+
+/// The inverse of `worktree_path`: the branch a yati worktree at `path` belongs to,
+/// or `None` if `path` is not laid out by this project's template.
+pub fn branch_from_path(config: &Config, project: &str, path: &Path) -> Result<Option<String>> {
+    let (prefix, suffix) = render_around_branch(config, project)?;
+    let path = path.to_string_lossy();
+    let branch = path
+        .strip_prefix(prefix.as_str())
+        .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+        .filter(|b| !b.is_empty() && !b.starts_with('/') && !b.ends_with('/'));
+    Ok(branch.map(str::to_string))
+}
+
+/// Remove `dir` and then each of its parents while they are empty, stopping after `root`.
+pub fn cleanup_empty_parents(dir: &Path, root: &Path) {
+    let mut current = Some(dir);
+    while let Some(path) = current {
+        if !path.starts_with(root) {
+            break;
+        }
+        let is_empty = std::fs::read_dir(path).is_ok_and(|mut e| e.next().is_none());
+        if !is_empty || std::fs::remove_dir(path).is_err() {
+            break;
+        }
+        if path == root {
+            break;
+        }
+        current = path.parent();
+    }
+}
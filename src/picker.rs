@@ -0,0 +1,130 @@
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// An entry offered by the picker.
+pub struct Item {
+    /// Returned when the item is picked.
+    pub key: String,
+    /// What is shown for the item.
+    pub label: String,
+    /// Repository whose recent commits are previewed.
+    pub preview_dir: PathBuf,
+}
+
+fn has_fzf() -> bool {
+    Command::new("fzf")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Let the user pick one of `items`, starting from `query`. Uses fzf with a preview of
+/// recent commits when it is installed, and a plain prompt otherwise.
+/// Returns the picked item's key, or `None` if the user cancelled.
+pub fn pick(items: &[Item], query: Option<&str>) -> Result<Option<String>> {
+    if items.is_empty() {
+        bail!("Nothing to pick from");
+    }
+    if has_fzf() {
+        pick_fzf(items, query)
+    } else {
+        pick_prompt(items, query)
+    }
+}
+
+fn pick_fzf(items: &[Item], query: Option<&str>) -> Result<Option<String>> {
+    let mut cmd = Command::new("fzf");
+    cmd.args([
+        "--delimiter=\t",
+        "--with-nth=2",
+        "--no-multi",
+        "--reverse",
+        "--prompt=worktree> ",
+        "--preview=git -C {3} log --oneline --decorate --color=always -n 30",
+        "--preview-window=down,50%",
+    ]);
+    if let Some(query) = query {
+        cmd.arg(format!("--query={}", query));
+    }
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run fzf")?;
+
+    {
+        let mut stdin = child.stdin.take().context("Failed to open fzf stdin")?;
+        for item in items {
+            writeln!(
+                stdin,
+                "{}\t{}\t{}",
+                item.key,
+                item.label,
+                item.preview_dir.display()
+            )
+            .context("Failed to write to fzf")?;
+        }
+    }
+
+    let output = child.wait_with_output().context("Failed to run fzf")?;
+    // fzf exits with 1 for no match and 130 when cancelled
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let selected = String::from_utf8_lossy(&output.stdout);
+    Ok(selected
+        .lines()
+        .next()
+        .and_then(|line| line.split('\t').next())
+        .map(str::to_string))
+}
+
+/// Whether the characters of `query` appear in `text` in order, ignoring case.
+fn fuzzy_matches(text: &str, query: &str) -> bool {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| chars.any(|c| c == q))
+}
+
+fn pick_prompt(items: &[Item], query: Option<&str>) -> Result<Option<String>> {
+    let stdin = std::io::stdin();
+    let mut query = query.unwrap_or_default().to_string();
+    loop {
+        let matches: Vec<&Item> = items
+            .iter()
+            .filter(|item| fuzzy_matches(&item.key, &query))
+            .collect();
+        if matches.len() == 1 && !query.is_empty() {
+            return Ok(Some(matches[0].key.clone()));
+        }
+        if matches.is_empty() {
+            println!("No worktrees match '{}'", query);
+        }
+        for (i, item) in matches.iter().enumerate() {
+            println!("{:>3}) {}", i + 1, item.label);
+        }
+        print!("Pick a number or type to filter (empty to cancel): ");
+        std::io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).context("Failed to read input")? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if let Ok(n) = line.parse::<usize>()
+            && (1..=matches.len()).contains(&n)
+        {
+            return Ok(Some(matches[n - 1].key.clone()));
+        }
+        query = line.to_string();
+    }
+}
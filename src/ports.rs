@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::net::TcpListener;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::paths;
+
+/// Range ports are handed out from when `port_range` isn't configured.
+const DEFAULT_RANGE: [u16; 2] = [4000, 4999];
+
+/// Ports reserved per worktree, keyed by session name (`project/branch`).
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PortRegistry {
+    pub allocations: BTreeMap<String, Vec<u16>>,
+}
+
+fn registry_path() -> Result<PathBuf> {
+    Ok(paths::yati_root()?.join("ports.toml"))
+}
+
+/// Take an exclusive lock on the registry, held until the returned file is dropped, so
+/// concurrent yati runs can't hand out the same ports.
+fn lock() -> Result<File> {
+    let path = paths::yati_root()?.join("ports.lock");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", path.display()))?;
+    Ok(file)
+}
+
+fn is_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+impl PortRegistry {
+    pub fn load() -> Result<PortRegistry> {
+        let path = registry_path()?;
+        if !path.exists() {
+            return Ok(PortRegistry::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write the registry through a temporary file, so readers that don't take the lock
+    /// never see it half-written.
+    pub fn save(&self) -> Result<()> {
+        let path = registry_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let contents = toml::to_string(self).context("Failed to serialize port registry")?;
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, contents)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, session: &str) -> &[u16] {
+        self.allocations
+            .get(session)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// The ports reserved for `session`, reserving `ports` of them from `port_range` first
+/// if it doesn't hold enough yet. Ports held by another worktree or already bound by
+/// some other process are skipped.
+pub fn ensure(session: &str, config: &Config) -> Result<Vec<u16>> {
+    let _lock = lock()?;
+    let mut registry = PortRegistry::load()?;
+    let held = allocate(&registry, session, config)?;
+    if held != registry.get(session) {
+        registry.allocations.insert(session.to_string(), held.clone());
+        registry.save()?;
+    }
+    Ok(held)
+}
+
+/// The ports `ensure` would reserve for `session`, without reserving them.
+pub fn preview(session: &str, config: &Config) -> Result<Vec<u16>> {
+    allocate(&PortRegistry::load()?, session, config)
+}
+
+fn allocate(registry: &PortRegistry, session: &str, config: &Config) -> Result<Vec<u16>> {
+    let count = config.ports.unwrap_or(0) as usize;
+    let mut held = registry.get(session).to_vec();
+    if held.len() >= count {
+        return Ok(held);
+    }
+
+    let [start, end] = config.port_range.unwrap_or(DEFAULT_RANGE);
+    let taken: Vec<u16> = registry.allocations.values().flatten().copied().collect();
+    for port in start..=end {
+        if held.len() == count {
+            break;
+        }
+        if !taken.contains(&port) && is_free(port) {
+            held.push(port);
+        }
+    }
+    if held.len() < count {
+        bail!(
+            "Not enough free ports in {}-{} to reserve {} for '{}'",
+            start,
+            end,
+            count,
+            session
+        );
+    }
+    Ok(held)
+}
+
+/// Free the ports reserved for `session`.
+pub fn release(session: &str) -> Result<()> {
+    let _lock = lock()?;
+    let mut registry = PortRegistry::load()?;
+    if registry.allocations.remove(session).is_some() {
+        registry.save()?;
+    }
+    Ok(())
+}
+
+/// Move the ports reserved for `old` over to `new`.
+pub fn rename(old: &str, new: &str) -> Result<()> {
+    let _lock = lock()?;
+    let mut registry = PortRegistry::load()?;
+    if let Some(held) = registry.allocations.remove(old) {
+        registry.allocations.insert(new.to_string(), held);
+        registry.save()?;
+    }
+    Ok(())
+}
+
+/// Format ports for display, e.g. `4000, 4001`.
+pub fn join(ports: &[u16]) -> String {
+    ports
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
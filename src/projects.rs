@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::{git, paths, worktrees};
+
+/// A project known to yati, identified by the path of its main worktree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectEntry {
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+}
+
+/// Project names registered under the yati root, so that repos sharing a directory
+/// name don't end up sharing worktree directories and tmux sessions.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Registry {
+    pub projects: BTreeMap<String, ProjectEntry>,
+}
+
+fn registry_path() -> Result<PathBuf> {
+    Ok(paths::yati_root()?.join("projects.toml"))
+}
+
+impl Registry {
+    pub fn load() -> Result<Registry> {
+        let path = registry_path()?;
+        if !path.exists() {
+            return Ok(Registry::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = registry_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let contents = toml::to_string(self).context("Failed to serialize project registry")?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Name registered for the project whose main worktree is at `main_worktree`.
+    pub fn name_for_path(&self, main_worktree: &Path) -> Option<&str> {
+        self.projects
+            .iter()
+            .find(|(_, entry)| entry.path == main_worktree)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Name of the project `main_worktree` is a moved clone of: one registered with the
+    /// same `origin` URL whose path is gone.
+    pub fn moved_from(&self, main_worktree: &Path) -> Option<&str> {
+        let remote = git::remote_url(main_worktree)?;
+        self.projects
+            .iter()
+            .find(|(_, e)| e.remote.as_ref() == Some(&remote) && !e.path.exists())
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+fn default_name(main_worktree: &Path) -> Result<String> {
+    Ok(main_worktree
+        .file_name()
+        .context("Could not determine repo name")?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Error for a repository whose project name belongs to another repository.
+fn name_taken(name: &str, owner: &ProjectEntry, main_worktree: &Path) -> anyhow::Error {
+    anyhow!(
+        "Project name '{}' is already used by {}.\n\
+         Set `project_name` in {} to give this repository a different name.",
+        name,
+        owner.path.display(),
+        main_worktree.join("yati.toml").display()
+    )
+}
+
+/// The project name for a main worktree: `project_name` from its config, then the name
+/// it was registered under (or that the clone it was moved from was), then the
+/// directory name, unless another repository is registered under that.
+pub fn name_for(main_worktree: &Path, config: &Config, registry: &Registry) -> Result<String> {
+    if let Some(name) = &config.project_name {
+        return Ok(name.clone());
+    }
+    if let Some(name) = registry
+        .name_for_path(main_worktree)
+        .or_else(|| registry.moved_from(main_worktree))
+    {
+        return Ok(name.to_string());
+    }
+    let name = default_name(main_worktree)?;
+    match registry.projects.get(&name) {
+        Some(owner) => Err(name_taken(&name, owner, main_worktree)),
+        None => Ok(name),
+    }
+}
+
+/// Resolve the project name for `main_worktree` and record it in the registry,
+/// failing if another repository already owns that name.
+pub fn register(main_worktree: &Path, config: &Config) -> Result<String> {
+    let mut registry = Registry::load()?;
+    let remote = git::remote_url(main_worktree);
+
+    // A clone that moved keeps its name if its remote matches an entry whose path is gone
+    if config.project_name.is_none()
+        && registry.name_for_path(main_worktree).is_none()
+        && let Some(name) = registry.moved_from(main_worktree).map(str::to_string)
+        && let Some(entry) = registry.projects.get_mut(&name)
+    {
+        entry.path = main_worktree.to_path_buf();
+    }
+
+    let name = name_for(main_worktree, config, &registry)?;
+
+    // Worktrees created before the registry existed still claim their directory name
+    if !registry.projects.contains_key(&name)
+        && let Some(owner) = worktrees::discover()?
+            .into_iter()
+            .find(|w| w.project == name && w.main_worktree != main_worktree)
+    {
+        registry.projects.insert(
+            name.clone(),
+            ProjectEntry {
+                remote: git::remote_url(&owner.main_worktree),
+                path: owner.main_worktree,
+            },
+        );
+        registry.save()?;
+    }
+
+    if let Some(entry) = registry.projects.get(&name)
+        && entry.path != main_worktree
+    {
+        return Err(name_taken(&name, entry, main_worktree));
+    }
+
+    // The name may have changed through a `project_name` override; drop the stale entry
+    registry
+        .projects
+        .retain(|n, e| n == &name || e.path != main_worktree);
+    registry.projects.insert(
+        name.clone(),
+        ProjectEntry {
+            path: main_worktree.to_path_buf(),
+            remote,
+        },
+    );
+    registry.save()?;
+    Ok(name)
+}
@@ -0,0 +1,21 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The current Unix timestamp.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Short human-readable age of a Unix timestamp, e.g. `5m`, `3h`, `2d`.
+pub fn format_age(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s if s < 86400 * 14 => format!("{}d", s / 86400),
+        s => format!("{}w", s / (86400 * 7)),
+    }
+}
@@ -1,23 +1,36 @@
 use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::process::Command;
 
 use crate::config::WindowConfig;
+use crate::multiplexer::Multiplexer;
 
 pub fn is_in_tmux() -> bool {
     std::env::var("TMUX").is_ok()
 }
 
-pub fn new_session(name: &str, working_dir: &Path) -> Result<()> {
-    let output = Command::new("tmux")
-        .args([
-            "new-session",
-            "-d",
-            "-s",
-            name,
-            "-c",
-            &working_dir.to_string_lossy(),
-        ])
+/// Add `-e KEY=VALUE` for each variable of `env`.
+fn env_args<'a>(cmd: &mut Command, env: impl IntoIterator<Item = (&'a String, &'a String)>) {
+    for (key, value) in env {
+        cmd.arg("-e").arg(format!("{}={}", key, value));
+    }
+}
+
+/// Create a detached session. `env` is set in the session environment, so every pane
+/// created in the session inherits it.
+pub fn new_session(name: &str, working_dir: &Path, env: &[(String, String)]) -> Result<()> {
+    let mut cmd = Command::new("tmux");
+    cmd.args([
+        "new-session",
+        "-d",
+        "-s",
+        name,
+        "-c",
+        &working_dir.to_string_lossy(),
+    ]);
+    env_args(&mut cmd, env.iter().map(|(k, v)| (k, v)));
+    let output = cmd
         .output()
         .context("Failed to run tmux new-session")?;
     if !output.status.success() {
@@ -30,6 +43,38 @@ pub fn new_session(name: &str, working_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Set `env` in the session environment, so panes created from now on see it.
+pub fn set_environment(session: &str, env: &[(String, String)]) -> Result<()> {
+    for (key, value) in env {
+        let output = Command::new("tmux")
+            .args(["set-environment", "-t", session, key, value])
+            .output()
+            .context("Failed to run tmux set-environment")?;
+        if !output.status.success() {
+            bail!(
+                "tmux set-environment failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Name of the session the current client is in, if running inside tmux.
+pub fn current_session() -> Option<String> {
+    if !is_in_tmux() {
+        return None;
+    }
+    let output = Command::new("tmux")
+        .args(["display-message", "-p", "#{session_name}"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 pub fn session_exists(name: &str) -> bool {
     Command::new("tmux")
         .args(["has-session", "-t", name])
@@ -38,6 +83,25 @@ pub fn session_exists(name: &str) -> bool {
         .unwrap_or(false)
 }
 
+/// Last activity time (Unix timestamp) of every running session, keyed by session name.
+/// Empty if no tmux server is running.
+pub fn session_activity() -> HashMap<String, u64> {
+    let output = match Command::new("tmux")
+        .args(["list-sessions", "-F", "#{session_activity} #{session_name}"])
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return HashMap::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (activity, name) = line.split_once(' ')?;
+            Some((name.to_string(), activity.parse().ok()?))
+        })
+        .collect()
+}
+
 pub fn attach_or_switch(name: &str) -> Result<()> {
     if is_in_tmux() {
         let output = Command::new("tmux")
@@ -87,33 +151,143 @@ pub fn rename_window(session: &str, name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Create a window with `env` set in its pane and return the pane's id.
 pub fn create_window(
     session: &str,
     name: &str,
     working_dir: &Path,
-    command: Option<&str>,
-) -> Result<()> {
-    let output = Command::new("tmux")
-        .args([
+    env: &BTreeMap<String, String>,
+) -> Result<String> {
+    let mut cmd = Command::new("tmux");
+    cmd.args([
             "new-window",
+            "-P",
+            "-F",
+            "#{pane_id}",
             "-t",
             session,
             "-n",
             name,
             "-c",
             &working_dir.to_string_lossy(),
+        ]);
+    env_args(&mut cmd, env);
+    let output = cmd.output().context("Failed to run tmux new-window")?;
+    if !output.status.success() {
+        bail!(
+            "tmux new-window failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The id of the active pane of `target`.
+fn active_pane(target: &str) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(["display-message", "-p", "-t", target, "#{pane_id}"])
+        .output()
+        .context("Failed to run tmux display-message")?;
+    if !output.status.success() {
+        bail!(
+            "tmux display-message failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Restart the shell in `pane` in another directory, with `env` added.
+fn respawn_pane(pane: &str, working_dir: &Path, env: &BTreeMap<String, String>) -> Result<()> {
+    let mut cmd = Command::new("tmux");
+    cmd.args(["respawn-pane", "-k", "-t", pane, "-c", &working_dir.to_string_lossy()]);
+    env_args(&mut cmd, env);
+    let output = cmd.output().context("Failed to run tmux respawn-pane")?;
+    if !output.status.success() {
+        bail!(
+            "tmux respawn-pane failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Split `pane` side by side (`horizontal`) or stacked, with `env` set in the new pane,
+/// and return the new pane's id.
+pub fn split_window(
+    pane: &str,
+    working_dir: &Path,
+    horizontal: bool,
+    env: &BTreeMap<String, String>,
+) -> Result<String> {
+    let mut cmd = Command::new("tmux");
+    cmd.args([
+            "split-window",
+            if horizontal { "-h" } else { "-v" },
+            "-P",
+            "-F",
+            "#{pane_id}",
+            "-t",
+            pane,
+            "-c",
+            &working_dir.to_string_lossy(),
+        ]);
+    env_args(&mut cmd, env);
+    let output = cmd.output().context("Failed to run tmux split-window")?;
+    if !output.status.success() {
+        bail!(
+            "tmux split-window failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn select_layout(target: &str, layout: &str) -> Result<()> {
+    let output = Command::new("tmux")
+        .args(["select-layout", "-t", target, layout])
+        .output()
+        .context("Failed to run tmux select-layout")?;
+    if !output.status.success() {
+        bail!(
+            "tmux select-layout failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Set the width (`horizontal`) or height of `pane`, in cells or as a percentage.
+fn resize_pane(pane: &str, horizontal: bool, size: &str) -> Result<()> {
+    let output = Command::new("tmux")
+        .args([
+            "resize-pane",
+            "-t",
+            pane,
+            if horizontal { "-x" } else { "-y" },
+            size,
         ])
         .output()
-        .context("Failed to run tmux new-window")?;
+        .context("Failed to run tmux resize-pane")?;
     if !output.status.success() {
         bail!(
-            "tmux new-window failed: {}",
+            "tmux resize-pane failed: {}",
             String::from_utf8_lossy(&output.stderr).trim()
         );
     }
-    if let Some(cmd) = command {
-        let target = format!("{}:{}", session, name);
-        send_keys(&target, cmd)?;
+    Ok(())
+}
+
+pub fn select_pane(pane: &str) -> Result<()> {
+    let output = Command::new("tmux")
+        .args(["select-pane", "-t", pane])
+        .output()
+        .context("Failed to run tmux select-pane")?;
+    if !output.status.success() {
+        bail!(
+            "tmux select-pane failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
     }
     Ok(())
 }
@@ -152,18 +326,62 @@ pub fn setup_windows(session: &str, working_dir: &Path, windows: &[WindowConfig]
         return Ok(());
     }
 
-    let first = &windows[0];
-    rename_window(session, &first.name)?;
-    if let Some(cmd) = &first.command {
-        let target = format!("{}:{}", session, first.name);
-        send_keys(&target, cmd)?;
-    }
+    for (i, window) in windows.iter().enumerate() {
+        let env = &window.env;
+        let first_dir =
+            window.pane_dir(working_dir, window.panes.first().and_then(|p| p.cwd.as_deref()))?;
+        let first = if i == 0 {
+            // The session's first pane already runs a shell, started in the worktree
+            rename_window(session, &window.name)?;
+            let pane = active_pane(session)?;
+            if first_dir != working_dir || !env.is_empty() {
+                respawn_pane(&pane, &first_dir, env)?;
+            }
+            pane
+        } else {
+            create_window(session, &window.name, &first_dir, env)?
+        };
+
+        if window.panes.is_empty() {
+            if let Some(cmd) = &window.command {
+                send_keys(&first, cmd)?;
+            }
+            continue;
+        }
+
+        let layout = window.layout.as_deref();
+        let horizontal = window.splits_horizontally();
+        let mut ids = vec![first];
+        for pane in &window.panes[1..] {
+            let id = split_window(
+                &ids[ids.len() - 1],
+                &window.pane_dir(working_dir, pane.cwd.as_deref())?,
+                horizontal,
+                env,
+            )?;
+            // Spread the panes out after each split so there is room for the next one
+            select_layout(&id, "tiled")?;
+            ids.push(id);
+        }
+        let default_layout = if horizontal { "even-horizontal" } else { "even-vertical" };
+        select_layout(&ids[0], layout.unwrap_or(default_layout))?;
 
-    for window in &windows[1..] {
-        create_window(session, &window.name, working_dir, window.command.as_deref())?;
+        let mut focus = &ids[0];
+        for (id, pane) in ids.iter().zip(&window.panes) {
+            if let Some(size) = &pane.size {
+                resize_pane(id, horizontal, size)?;
+            }
+            if let Some(cmd) = &pane.command {
+                send_keys(id, cmd)?;
+            }
+            if pane.focus {
+                focus = id;
+            }
+        }
+        select_pane(focus)?;
     }
 
-    select_window(session, &first.name)?;
+    select_window(session, &windows[0].name)?;
 
     Ok(())
 }
@@ -229,6 +447,52 @@ pub fn kill_other_panes(session: &str) -> Result<()> {
     Ok(())
 }
 
+/// The panes of `session` as `(pane id, description)`, e.g.
+/// `("%3", "r/feature:1.0 editor: nvim")`.
+pub fn panes(session: &str) -> Result<Vec<(String, String)>> {
+    let output = Command::new("tmux")
+        .args([
+            "list-panes",
+            "-s",
+            "-t",
+            session,
+            "-F",
+            "#{pane_id} #{session_name}:#{window_index}.#{pane_index} #{window_name}: #{pane_current_command}",
+        ])
+        .output()
+        .context("Failed to run tmux list-panes")?;
+    if !output.status.success() {
+        bail!(
+            "tmux list-panes failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(id, description)| (id.to_string(), description.to_string()))
+        .collect())
+}
+
+/// The id of the pane this process runs in, if inside tmux.
+pub fn current_pane() -> Option<String> {
+    std::env::var("TMUX_PANE").ok()
+}
+
+pub fn rename_session(old: &str, new: &str) -> Result<()> {
+    let output = Command::new("tmux")
+        .args(["rename-session", "-t", old, new])
+        .output()
+        .context("Failed to run tmux rename-session")?;
+    if !output.status.success() {
+        bail!(
+            "tmux rename-session failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
 pub fn kill_session(name: &str) -> Result<()> {
     let output = Command::new("tmux")
         .args(["kill-session", "-t", name])
@@ -242,3 +506,84 @@ pub fn kill_session(name: &str) -> Result<()> {
     }
     Ok(())
 }
+
+/// tmux, the default multiplexer.
+pub struct Tmux;
+
+impl Multiplexer for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn is_inside(&self) -> bool {
+        is_in_tmux()
+    }
+
+    fn current_session(&self) -> Option<String> {
+        current_session()
+    }
+
+    fn session_exists(&self, name: &str) -> bool {
+        session_exists(name)
+    }
+
+    fn sessions(&self) -> HashMap<String, Option<u64>> {
+        session_activity()
+            .into_iter()
+            .map(|(name, activity)| (name, Some(activity)))
+            .collect()
+    }
+
+    fn new_session(&self, name: &str, working_dir: &Path, env: &[(String, String)]) -> Result<()> {
+        new_session(name, working_dir, env)
+    }
+
+    fn set_environment(&self, session: &str, env: &[(String, String)]) -> Result<()> {
+        set_environment(session, env)
+    }
+
+    fn setup_windows(
+        &self,
+        session: &str,
+        working_dir: &Path,
+        windows: &[WindowConfig],
+    ) -> Result<()> {
+        setup_windows(session, working_dir, windows)
+    }
+
+    fn attach_or_switch(&self, name: &str) -> Result<()> {
+        attach_or_switch(name)
+    }
+
+    fn switch_to_previous_session(&self) -> bool {
+        switch_to_previous_session()
+    }
+
+    fn detach(&self) -> Result<()> {
+        detach()
+    }
+
+    fn can_kill_other_panes(&self) -> bool {
+        true
+    }
+
+    fn kill_other_panes(&self, session: &str) -> Result<()> {
+        kill_other_panes(session)
+    }
+
+    fn kill_session(&self, name: &str) -> Result<()> {
+        kill_session(name)
+    }
+
+    fn rename_session(&self, old: &str, new: &str) -> Result<()> {
+        rename_session(old, new)
+    }
+
+    fn panes(&self, session: &str) -> Result<Vec<(String, String)>> {
+        panes(session)
+    }
+
+    fn current_pane(&self) -> Option<String> {
+        current_pane()
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::time::now;
+use crate::projects::Registry;
+use crate::worktrees::Worktree;
+use crate::{git, paths};
+
+/// Trashed worktrees are kept as `refs/yati/trash/<project>/<branch>/<timestamp>`.
+const PREFIX: &str = "refs/yati/trash/";
+
+/// Trailer in the trash commit recording the branch's base ref.
+const BASE_TRAILER: &str = "Yati-Base: ";
+
+/// A torn-down worktree kept in the trash.
+///
+/// The ref points to a commit holding the worktree's files as they were, uncommitted
+/// and untracked changes included, whose parent is the branch tip.
+pub struct Entry {
+    pub project: String,
+    pub branch: String,
+    pub timestamp: u64,
+    pub refname: String,
+    pub main_worktree: PathBuf,
+}
+
+impl Entry {
+    fn parse(refname: &str, main_worktree: &Path) -> Option<Entry> {
+        let rest = refname.strip_prefix(PREFIX)?;
+        let (project, rest) = rest.split_once('/')?;
+        let (branch, timestamp) = rest.rsplit_once('/')?;
+        Some(Entry {
+            project: project.to_string(),
+            branch: branch.to_string(),
+            timestamp: timestamp.parse().ok()?,
+            refname: refname.to_string(),
+            main_worktree: main_worktree.to_path_buf(),
+        })
+    }
+
+    /// `project/branch`, as accepted by `yati restore`.
+    pub fn target(&self) -> String {
+        format!("{}/{}", self.project, self.branch)
+    }
+
+    /// Archive of the ignored files that were copied into the worktree, if kept.
+    pub fn tarball(&self) -> Result<PathBuf> {
+        Ok(trash_dir()?
+            .join(&self.project)
+            .join(&self.branch)
+            .join(format!("{}.tar.gz", self.timestamp)))
+    }
+
+    /// The base ref the branch had when it was trashed.
+    pub fn base(&self) -> Option<String> {
+        git::commit_body(&self.main_worktree, &self.refname)?
+            .lines()
+            .find_map(|line| line.strip_prefix(BASE_TRAILER))
+            .map(str::to_string)
+    }
+
+    /// Whether the worktree had uncommitted or untracked changes.
+    pub fn has_changes(&self) -> bool {
+        !git::same_tree_as_parent(&self.main_worktree, &self.refname)
+    }
+}
+
+pub fn trash_dir() -> Result<PathBuf> {
+    Ok(paths::yati_root()?.join("trash"))
+}
+
+/// Trash entries of the repository whose main worktree is `main_worktree`.
+pub fn entries(main_worktree: &Path) -> Vec<Entry> {
+    git::list_refs(main_worktree, PREFIX)
+        .iter()
+        .filter_map(|r| Entry::parse(r, main_worktree))
+        .collect()
+}
+
+/// Trash entries of every registered project and the current repository, oldest first
+/// per worktree.
+pub fn all_entries() -> Vec<Entry> {
+    let mut repos: Vec<PathBuf> = Registry::load()
+        .map(|r| r.projects.into_values().map(|p| p.path).collect())
+        .unwrap_or_default();
+    if let Ok(main) = git::main_worktree_root() {
+        repos.push(main);
+    }
+    repos.sort();
+    repos.dedup();
+
+    let mut all: Vec<Entry> = repos.iter().flat_map(|repo| entries(repo)).collect();
+    all.sort_by_key(|e| (e.target(), e.timestamp));
+    all
+}
+
+/// Move the state of `worktree` into the trash: its branch, uncommitted and untracked
+/// changes, and those of the `ignored` paths that exist and are ignored by git.
+pub fn save(worktree: &Worktree, base: Option<&str>, ignored: &[String]) -> Result<Entry> {
+    let mut message = format!("yati trash: {}\n\n", worktree.session_name());
+    if let Some(base) = base {
+        message.push_str(&format!("{}{}\n", BASE_TRAILER, base));
+    }
+    let commit = git::commit_worktree_state(&worktree.path, &message)?;
+    // Entries are told apart by their timestamp, so one trashed within the same second
+    // as an earlier one takes the next free second
+    let mut timestamp = now();
+    let refname = loop {
+        let refname = format!(
+            "{}{}/{}/{}",
+            PREFIX, worktree.project, worktree.branch, timestamp
+        );
+        if git::resolve_commit(&worktree.main_worktree, &refname).is_none() {
+            break refname;
+        }
+        timestamp += 1;
+    };
+    git::create_ref(&worktree.main_worktree, &refname, &commit)?;
+    let entry = Entry::parse(&refname, &worktree.main_worktree)
+        .context("Invalid trash ref name")?;
+
+    let ignored: Vec<&String> = ignored
+        .iter()
+        .filter(|p| worktree.path.join(p).exists() && git::is_ignored(&worktree.path, p))
+        .collect();
+    if !ignored.is_empty() {
+        let tarball = entry.tarball()?;
+        if let Some(parent) = tarball.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let output = Command::new("tar")
+            .arg("-czf")
+            .arg(&tarball)
+            .arg("-C")
+            .arg(&worktree.path)
+            .arg("--")
+            .args(&ignored)
+            .output()
+            .context("Failed to run tar")?;
+        if !output.status.success() {
+            bail!(
+                "tar failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+
+    Ok(entry)
+}
+
+/// Unpack the ignored files kept for `entry` into `dir`, if there are any.
+pub fn extract_ignored(entry: &Entry, dir: &Path) -> Result<()> {
+    let tarball = entry.tarball()?;
+    if !tarball.exists() {
+        return Ok(());
+    }
+    let output = Command::new("tar")
+        .arg("-xzf")
+        .arg(&tarball)
+        .arg("-C")
+        .arg(dir)
+        .output()
+        .context("Failed to run tar")?;
+    if !output.status.success() {
+        bail!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Delete `entry` from the trash for good.
+pub fn remove(entry: &Entry) -> Result<()> {
+    git::delete_ref(&entry.main_worktree, &entry.refname)?;
+    let tarball = entry.tarball()?;
+    if tarball.exists() {
+        std::fs::remove_file(&tarball)
+            .with_context(|| format!("Failed to remove {}", tarball.display()))?;
+    }
+    if let Some(parent) = tarball.parent() {
+        paths::cleanup_empty_parents(parent, &trash_dir()?);
+    }
+    Ok(())
+}
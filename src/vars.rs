@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::{Config, PaneConfig, WindowConfig};
+use crate::worktrees::Worktree;
+
+/// Values describing a worktree, available as `{name}` placeholders in hooks, tmux window
+/// commands and copy paths, and as `YATI_<NAME>` environment variables.
+pub struct Vars {
+    entries: Vec<(String, String)>,
+    /// The config's `env`, exported alongside the `YATI_*` variables.
+    env: Vec<(String, String)>,
+}
+
+/// Lowercase `branch` and replace anything but letters, digits and `_` with single dashes,
+/// e.g. `feature/Login-Fix` becomes `feature-login-fix`.
+pub fn slugify(branch: &str) -> String {
+    let mut slug = String::new();
+    for c in branch.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Quote `value` for `sh`, unless it only contains characters that need no quoting.
+pub fn shell_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:@%+=,".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// The shell quotes a placeholder in a command sits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+/// Replace each `{name}` in `text` that `render` has a value for, scanning once from
+/// left to right, so values are never searched for placeholders themselves. `render`
+/// also gets the shell quoting the placeholder is in. Anything else in braces is left
+/// alone, so shell syntax like `${HOME}` or `{a,b}` passes through.
+pub fn substitute(text: &str, render: impl Fn(&str, Quoting) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut quoting = Quoting::None;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '{'
+            && let Some(end) = rest.find('}')
+            && let Some(value) = render(&rest[1..end], quoting)
+        {
+            result.push_str(&value);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let mut len = c.len_utf8();
+        match (quoting, c) {
+            (Quoting::None, '\\') | (Quoting::Double, '\\') => {
+                // The escaped character is taken as is
+                len += rest[len..].chars().next().map_or(0, char::len_utf8);
+            }
+            (Quoting::None, '\'') => quoting = Quoting::Single,
+            (Quoting::None, '"') => quoting = Quoting::Double,
+            (Quoting::Single, '\'') | (Quoting::Double, '"') => quoting = Quoting::None,
+            _ => {}
+        }
+        result.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    result
+}
+
+/// Quote `value` so it stays a single word where `quoting` is in effect.
+fn quote_in(value: &str, quoting: Quoting) -> String {
+    match quoting {
+        Quoting::None => shell_quote(value),
+        Quoting::Single => value.replace('\'', "'\\''"),
+        Quoting::Double => value
+            .chars()
+            .flat_map(|c| match c {
+                '\\' | '"' | '$' | '`' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect(),
+    }
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+impl Vars {
+    pub fn new(worktree: &Worktree, base_ref: Option<&str>) -> Vars {
+        Vars {
+            entries: vec![
+                ("branch".to_string(), worktree.branch.clone()),
+                ("branch_slug".to_string(), slugify(&worktree.branch)),
+                ("project".to_string(), worktree.project.clone()),
+                ("worktree_path".to_string(), path_str(&worktree.path)),
+                ("main_worktree".to_string(), path_str(&worktree.main_worktree)),
+                ("session".to_string(), worktree.session_name()),
+                (
+                    "base_ref".to_string(),
+                    base_ref.unwrap_or_default().to_string(),
+                ),
+            ],
+            env: Vec::new(),
+        }
+    }
+
+    /// Add the worktree's reserved ports as `port` (the first one) and `port_1`, `port_2`, ...
+    pub fn with_ports(mut self, ports: &[u16]) -> Vars {
+        if let Some(first) = ports.first() {
+            self.entries.push(("port".to_string(), first.to_string()));
+        }
+        for (i, port) in ports.iter().enumerate() {
+            self.entries
+                .push((format!("port_{}", i + 1), port.to_string()));
+        }
+        self
+    }
+
+    /// Add the session-level `env` of a config that `apply` filled in.
+    pub fn with_env(mut self, env: &BTreeMap<String, String>) -> Vars {
+        self.env = env.clone().into_iter().collect();
+        self
+    }
+
+    /// Replace known `{name}` placeholders. Anything else in braces is left alone, so shell
+    /// syntax like `${HOME}` or `{a,b}` passes through.
+    pub fn interpolate(&self, text: &str) -> String {
+        substitute(text, |name, _| self.get(name).map(str::to_string))
+    }
+
+    /// Like `interpolate`, for text run by a shell: values are quoted for the quotes they
+    /// sit in, if any, so a branch named e.g. `fix;rm -rf ~` stays a single word.
+    pub fn interpolate_command(&self, text: &str) -> String {
+        substitute(text, |name, quoting| {
+            self.get(name).map(|value| quote_in(value, quoting))
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The values as `YATI_<NAME>` environment variables, followed by the config's `env`.
+    pub fn env(&self) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (format!("YATI_{}", name.to_uppercase()), value.clone()))
+            .chain(self.env.iter().cloned())
+            .collect()
+    }
+
+    /// A copy of `config` with placeholders in hooks, window and pane commands,
+    /// directories and environment values, and copy paths filled in.
+    pub fn apply(&self, config: &Config) -> Config {
+        let all = |list: &[String]| list.iter().map(|s| self.interpolate(s)).collect();
+        let commands =
+            |list: &[String]| list.iter().map(|s| self.interpolate_command(s)).collect();
+        let env = |env: &BTreeMap<String, String>| {
+            env.iter()
+                .map(|(key, value)| (key.clone(), self.interpolate(value)))
+                .collect()
+        };
+        let mut config = config.clone();
+        config.copy_files = all(&config.copy_files);
+        config.exclude = all(&config.exclude);
+        config.post_create = commands(&config.post_create);
+        config.post_activate = commands(&config.post_activate);
+        config.pre_teardown = commands(&config.pre_teardown);
+        config.env = env(&config.env);
+        config.tmux.windows = config
+            .tmux
+            .windows
+            .iter()
+            .map(|w| WindowConfig {
+                command: w.command.as_deref().map(|c| self.interpolate_command(c)),
+                cwd: w.cwd.as_deref().map(|c| self.interpolate(c)),
+                env: env(&w.env),
+                panes: w
+                    .panes
+                    .iter()
+                    .map(|p| PaneConfig {
+                        command: p.command.as_deref().map(|c| self.interpolate_command(c)),
+                        cwd: p.cwd.as_deref().map(|c| self.interpolate(c)),
+                        ..p.clone()
+                    })
+                    .collect(),
+                ..w.clone()
+            })
+            .collect();
+        config
+    }
+}
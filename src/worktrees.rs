@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::metadata::Metadata;
+use crate::projects::{self, Registry};
+use crate::{git, paths};
+
+/// A yati-managed worktree.
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    pub project: String,
+    pub branch: String,
+    pub path: PathBuf,
+    /// Main worktree of the repository, where `yati.toml` lives.
+    pub main_worktree: PathBuf,
+}
+
+impl Worktree {
+    pub fn session_name(&self) -> String {
+        format!("{}/{}", self.project, self.branch)
+    }
+}
+
+/// Project name and config per main worktree, so discovery reads each `yati.toml` once.
+struct ProjectCache {
+    registry: Registry,
+    metadata: Metadata,
+    projects: HashMap<PathBuf, (String, Config)>,
+}
+
+impl ProjectCache {
+    fn new() -> Result<ProjectCache> {
+        Ok(ProjectCache {
+            registry: Registry::load()?,
+            metadata: Metadata::load_all(),
+            projects: HashMap::new(),
+        })
+    }
+
+    fn get(&mut self, main_worktree: &Path) -> Result<&(String, Config)> {
+        if !self.projects.contains_key(main_worktree) {
+            let config = config::load_config(main_worktree)?;
+            let project = projects::name_for(main_worktree, &config, &self.registry)?;
+            self.projects
+                .insert(main_worktree.to_path_buf(), (project, config));
+        }
+        Ok(&self.projects[main_worktree])
+    }
+}
+
+fn identify_with(cache: &mut ProjectCache, path: &Path) -> Result<Option<Worktree>> {
+    let main_worktree = git::main_worktree_of(path)?;
+    if main_worktree == path {
+        return Ok(None);
+    }
+    let (project, config) = cache.get(&main_worktree)?;
+    // The branch comes from the path rather than HEAD, which is detached mid-rebase.
+    // Adopted worktrees outside the layout are known by the path in their metadata.
+    let branch = paths::branch_from_path(config, project, path)?;
+    let project = project.clone();
+    let Some(branch) = branch.or_else(|| cache.metadata.adopted(&project, path)) else {
+        return Ok(None);
+    };
+    Ok(Some(Worktree {
+        project,
+        branch,
+        path: path.to_path_buf(),
+        main_worktree,
+    }))
+}
+
+/// Identify the yati worktree at `path`, or `None` if it isn't one.
+pub fn identify(path: &Path) -> Result<Option<Worktree>> {
+    identify_with(&mut ProjectCache::new()?, path)
+}
+
+/// The yati worktree containing the current directory.
+pub fn current() -> Result<Worktree> {
+    let toplevel = git::repo_root()?;
+    identify(&toplevel)?.with_context(|| {
+        format!(
+            "Not in a yati worktree ({} is not laid out by yati)",
+            toplevel.display()
+        )
+    })
+}
+
+/// All yati worktrees found under the yati root, plus those of registered projects and
+/// of the current repository (they may live under a project-specific root).
+/// Sorted by project, then branch.
+pub fn discover() -> Result<Vec<Worktree>> {
+    let mut cache = ProjectCache::new()?;
+    let mut candidates = Vec::new();
+    if let Ok(root) = paths::yati_root() {
+        find_worktree_dirs(&root, &mut candidates);
+    }
+    for entry in cache.registry.projects.values() {
+        if let Ok(entries) = git::worktree_list_from(&entry.path) {
+            candidates.extend(entries.into_iter().skip(1).map(|e| e.path));
+        }
+    }
+    if let Ok(entries) = git::worktree_list() {
+        candidates.extend(entries.into_iter().skip(1).map(|e| e.path));
+    }
+    candidates.sort();
+    candidates.dedup();
+
+    let mut worktrees: Vec<Worktree> = candidates
+        .iter()
+        .filter_map(|path| identify_with(&mut cache, path).ok().flatten())
+        .collect();
+    worktrees.sort_by(|a, b| (&a.project, &a.branch).cmp(&(&b.project, &b.branch)));
+    Ok(worktrees)
+}
+
+/// Every git worktree, main worktrees included, of registered projects and the current
+/// repository, paired with whether yati manages it. Worktrees yati doesn't manage are
+/// named after their checked-out branch, or `(detached)`.
+pub fn discover_all() -> Result<Vec<(Worktree, bool)>> {
+    let managed = discover()?;
+    let mut cache = ProjectCache::new()?;
+    let mut repos: Vec<PathBuf> = cache
+        .registry
+        .projects
+        .values()
+        .map(|p| p.path.clone())
+        .collect();
+    if let Ok(main) = git::main_worktree_root() {
+        repos.push(main);
+    }
+    repos.sort();
+    repos.dedup();
+
+    let mut all: Vec<(Worktree, bool)> = Vec::new();
+    for repo in &repos {
+        let Ok(entries) = git::worktree_list_from(repo) else {
+            continue;
+        };
+        let Ok((project, _)) = cache.get(repo) else {
+            continue;
+        };
+        for entry in entries {
+            let worktree = match managed.iter().find(|w| w.path == entry.path) {
+                Some(w) => (w.clone(), true),
+                None => (
+                    Worktree {
+                        project: project.clone(),
+                        branch: match entry.branch.as_str() {
+                            "" => "(detached)".to_string(),
+                            b => b.to_string(),
+                        },
+                        path: entry.path,
+                        main_worktree: repo.clone(),
+                    },
+                    false,
+                ),
+            };
+            all.push(worktree);
+        }
+    }
+    // Managed worktrees of repositories that are neither registered nor current
+    for w in &managed {
+        if !all.iter().any(|(a, _)| a.path == w.path) {
+            all.push((w.clone(), true));
+        }
+    }
+    all.sort_by(|(a, _), (b, _)| (&a.project, &a.branch).cmp(&(&b.project, &b.branch)));
+    Ok(all)
+}
+
+/// Recursively find worktree directories under `current`.
+/// A worktree is identified by containing a `.git` file (not directory).
+fn find_worktree_dirs(current: &Path, results: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(current) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let git_path = path.join(".git");
+        if git_path.exists() && git_path.is_file() {
+            // This is a worktree leaf
+            results.push(path);
+        } else {
+            // Recurse to handle branch names with slashes
+            find_worktree_dirs(&path, results);
+        }
+    }
+}
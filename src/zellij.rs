@@ -0,0 +1,289 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{PaneConfig, WindowConfig};
+use crate::multiplexer::Multiplexer;
+use crate::vars;
+
+/// Zellij, with windows set up as tabs from generated KDL layouts.
+///
+/// Zellij session names can't contain `/`, so `project/branch` is escaped as
+/// `project%2Fbranch`.
+pub struct Zellij;
+
+fn escape(name: &str) -> String {
+    name.replace('%', "%25").replace('/', "%2F")
+}
+
+fn unescape(name: &str) -> String {
+    name.replace("%2F", "/").replace("%25", "%")
+}
+
+fn zellij(session: Option<&str>) -> Command {
+    let mut cmd = Command::new("zellij");
+    if let Some(session) = session {
+        cmd.arg("--session").arg(escape(session));
+    }
+    cmd
+}
+
+fn run(mut cmd: Command, what: &str) -> Result<()> {
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run zellij {}", what))?;
+    if !output.status.success() {
+        bail!(
+            "zellij {} failed: {}",
+            what,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Quote `s` as a KDL string.
+fn kdl_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A KDL `pane` node in `window`. Like tmux's `send-keys`, the command runs in a shell
+/// that stays open after it exits. Layouts can't set environment variables, so the
+/// window's `env` is exported by that shell.
+fn pane_node(
+    working_dir: &Path,
+    window: &WindowConfig,
+    pane: &PaneConfig,
+    indent: &str,
+) -> Result<String> {
+    let dir = window.pane_dir(working_dir, pane.cwd.as_deref())?;
+    let mut node = format!("{}pane cwd={}", indent, kdl_string(&dir.to_string_lossy()));
+    if let Some(size) = &pane.size {
+        // Plain numbers are fixed sizes; percentages stay strings
+        match size.parse::<u32>() {
+            Ok(cells) => node.push_str(&format!(" size={}", cells)),
+            Err(_) => node.push_str(&format!(" size={}", kdl_string(size))),
+        }
+    }
+    if pane.focus {
+        node.push_str(" focus=true");
+    }
+    if pane.command.is_none() && window.env.is_empty() {
+        node.push('\n');
+        return Ok(node);
+    }
+    let mut script: String = window
+        .env
+        .iter()
+        .map(|(key, value)| format!("export {}={}; ", key, vars::shell_quote(value)))
+        .collect();
+    if let Some(command) = &pane.command {
+        script.push_str(&format!("{}; ", command));
+    }
+    script.push_str("exec \"${SHELL:-sh}\"");
+    node.push_str(" command=\"sh\" {\n");
+    node.push_str(&format!(
+        "{}    args \"-c\" {}\n{}}}\n",
+        indent,
+        kdl_string(&script),
+        indent
+    ));
+    Ok(node)
+}
+
+/// A layout for one window. Panes are split side by side for the layouts tmux splits
+/// that way, and stacked otherwise; Zellij has no equivalent of custom tmux layouts.
+fn window_layout(working_dir: &Path, window: &WindowConfig) -> Result<String> {
+    if window.panes.is_empty() {
+        let pane = PaneConfig {
+            command: window.command.clone(),
+            ..PaneConfig::default()
+        };
+        return Ok(format!(
+            "layout {{\n{}}}\n",
+            pane_node(working_dir, window, &pane, "    ")?
+        ));
+    }
+    let direction = if window.splits_horizontally() {
+        "vertical"
+    } else {
+        "horizontal"
+    };
+    let panes: String = window
+        .panes
+        .iter()
+        .map(|pane| pane_node(working_dir, window, pane, "        "))
+        .collect::<Result<_>>()?;
+    Ok(format!(
+        "layout {{\n    pane split_direction=\"{}\" {{\n{}    }}\n}}\n",
+        direction, panes
+    ))
+}
+
+fn layout_file(session: &str, index: usize) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "yati-{}-{}-{}.kdl",
+        std::process::id(),
+        escape(session).replace('%', "_"),
+        index
+    ))
+}
+
+impl Multiplexer for Zellij {
+    fn name(&self) -> &'static str {
+        "zellij"
+    }
+
+    fn is_inside(&self) -> bool {
+        std::env::var("ZELLIJ").is_ok()
+    }
+
+    fn current_session(&self) -> Option<String> {
+        if !self.is_inside() {
+            return None;
+        }
+        std::env::var("ZELLIJ_SESSION_NAME").ok().map(|n| unescape(&n))
+    }
+
+    fn session_exists(&self, name: &str) -> bool {
+        self.sessions().contains_key(name)
+    }
+
+    /// Zellij doesn't report activity, only creation times.
+    fn sessions(&self) -> HashMap<String, Option<u64>> {
+        let output = match zellij(None)
+            .args(["list-sessions", "--no-formatting"])
+            .output()
+        {
+            Ok(o) if o.status.success() => o,
+            _ => return HashMap::new(),
+        };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            // Exited sessions can be resurrected, but aren't running
+            .filter(|line| !line.contains("EXITED"))
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|name| (unescape(name), None))
+            .collect()
+    }
+
+    fn new_session(&self, name: &str, working_dir: &Path, env: &[(String, String)]) -> Result<()> {
+        // The server inherits the environment of the process that starts it, and every
+        // pane inherits it from the server
+        let mut cmd = zellij(None);
+        cmd.args(["attach", "--create-background"])
+            .arg(escape(name))
+            .args(["options", "--default-cwd"])
+            .arg(working_dir)
+            .current_dir(working_dir)
+            .envs(env.iter().map(|(k, v)| (k, v)));
+        run(cmd, "attach --create-background")
+    }
+
+    /// Zellij sessions have no environment of their own; panes keep the one the server
+    /// was started with, so changes only reach new sessions.
+    fn set_environment(&self, session: &str, _env: &[(String, String)]) -> Result<()> {
+        eprintln!(
+            "Warning: zellij can't update the environment of a running session; changes to `env` and ports apply once '{}' is recreated",
+            session
+        );
+        Ok(())
+    }
+
+    /// Each window becomes a tab made from a KDL layout. The tab the session started
+    /// with is closed afterwards.
+    fn setup_windows(
+        &self,
+        session: &str,
+        working_dir: &Path,
+        windows: &[WindowConfig],
+    ) -> Result<()> {
+        if windows.is_empty() {
+            return Ok(());
+        }
+
+        for (i, window) in windows.iter().enumerate() {
+            let path = layout_file(session, i);
+            std::fs::write(&path, window_layout(working_dir, window)?)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            let mut cmd = zellij(Some(session));
+            cmd.args(["action", "new-tab", "--layout"])
+                .arg(&path)
+                .args(["--name", &window.name, "--cwd"])
+                .arg(working_dir);
+            let result = run(cmd, "action new-tab");
+            let _ = std::fs::remove_file(&path);
+            result?;
+        }
+
+        let mut cmd = zellij(Some(session));
+        cmd.args(["action", "go-to-tab", "1"]);
+        run(cmd, "action go-to-tab")?;
+        let mut cmd = zellij(Some(session));
+        cmd.args(["action", "close-tab"]);
+        run(cmd, "action close-tab")?;
+        let mut cmd = zellij(Some(session));
+        cmd.args(["action", "go-to-tab-name", &windows[0].name]);
+        run(cmd, "action go-to-tab-name")
+    }
+
+    fn attach_or_switch(&self, name: &str) -> Result<()> {
+        if self.is_inside() {
+            bail!(
+                "Zellij can't switch sessions from the command line; detach and run `zellij attach {}`",
+                escape(name)
+            );
+        }
+        let status = zellij(None)
+            .arg("attach")
+            .arg(escape(name))
+            .status()
+            .context("Failed to run zellij attach")?;
+        if !status.success() {
+            bail!("zellij attach failed");
+        }
+        Ok(())
+    }
+
+    /// Zellij has no notion of a previous session.
+    fn switch_to_previous_session(&self) -> bool {
+        false
+    }
+
+    fn detach(&self) -> Result<()> {
+        let mut cmd = zellij(None);
+        cmd.args(["action", "detach"]);
+        run(cmd, "action detach")
+    }
+
+    /// Zellij can't close panes other than the focused one from the command line.
+    fn can_kill_other_panes(&self) -> bool {
+        false
+    }
+
+    fn kill_other_panes(&self, _session: &str) -> Result<()> {
+        bail!("Zellij can't close the other panes of a session from the command line")
+    }
+
+    fn kill_session(&self, name: &str) -> Result<()> {
+        let mut cmd = zellij(None);
+        cmd.args(["delete-session", "--force"]).arg(escape(name));
+        run(cmd, "delete-session")
+    }
+
+    fn rename_session(&self, old: &str, new: &str) -> Result<()> {
+        let mut cmd = zellij(Some(old));
+        cmd.args(["action", "rename-session"]).arg(escape(new));
+        run(cmd, "action rename-session")
+    }
+
+    /// Zellij can't list panes from the command line.
+    fn panes(&self, _session: &str) -> Result<Vec<(String, String)>> {
+        Ok(Vec::new())
+    }
+
+    fn current_pane(&self) -> Option<String> {
+        std::env::var("ZELLIJ_PANE_ID").ok()
+    }
+}